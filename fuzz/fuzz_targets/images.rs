@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use safe_writing_rs::cipher::parse_images;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parse_images(data);
+});
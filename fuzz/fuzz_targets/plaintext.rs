@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use safe_writing_rs::cipher::parse_plaintext;
+
+// What a .safe file decrypts to, i.e., what an attacker controls once they
+// know the password, or what a bug in an older version may have written.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(plaintext) = parse_plaintext(data.to_vec()) {
+        // Whatever parses must survive the round trip
+        let reparsed = parse_plaintext(plaintext.encode()).unwrap();
+        assert_eq!(reparsed.num_passages(), plaintext.num_passages());
+        assert_eq!(reparsed.images(), plaintext.images());
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use safe_writing_rs::png::read_png_metadata;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = read_png_metadata(&data.to_vec());
+});
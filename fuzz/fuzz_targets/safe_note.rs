@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use safe_writing_rs::safe_note::{decrypt_safe_notes_ciphertext, parse_safe_note_file};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(contents) = std::str::from_utf8(data) {
+        let _ = parse_safe_note_file("password", contents);
+        let _ = decrypt_safe_notes_ciphertext("password", contents);
+    }
+});
@@ -0,0 +1,299 @@
+//! An ssh-agent like daemon that keeps the keys of opened files for a while,
+//! so that the password does not have to be typed for every file and every
+//! CLI call. It listens on a Unix socket in the config dir that only the
+//! owner can connect to, and keeps the keys in memory that is never swapped
+//! out.
+//!
+//! Each connection carries one request and gets one response back. The key
+//! travels as raw bytes, so that it is never copied into text on the way.
+//! A request is an operation byte, then the file name prefixed by its length
+//! as a little endian u32, then for `OP_PUT` the key. A response is a status
+//! byte, followed by the key for `STATUS_KEY`.
+
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    os::unix::{
+        fs::{DirBuilderExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{cipher::Key, config::Config, error::Error};
+
+const OP_GET: u8 = 0;
+const OP_PUT: u8 = 1;
+const OP_FORGET: u8 = 2;
+const OP_LOCK_ALL: u8 = 3;
+
+const STATUS_OK: u8 = 0;
+const STATUS_NOT_FOUND: u8 = 1;
+const STATUS_KEY: u8 = 2;
+
+/// Longer file names are refused rather than read into memory.
+const MAX_FILE_NAME_LEN: usize = 4096;
+
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn map_err(err: std::io::Error) -> Error {
+    Error::Agent(format!("{:?}", err))
+}
+
+pub fn socket_path() -> PathBuf {
+    Config::config_dir().join("agent.sock")
+}
+
+/// Talks to the agent listening on a socket.
+pub struct Client {
+    path: PathBuf,
+}
+
+impl Client {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Send a request and read the status byte of the response, leaving
+    /// the rest of the response in the stream.
+    fn request(&self, op: u8, file: &str, key: Option<&Key>) -> Result<(UnixStream, u8), Error> {
+        let mut stream = UnixStream::connect(&self.path).map_err(map_err)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT)).map_err(map_err)?;
+        let mut header = vec![op];
+        header.extend_from_slice(&(file.len() as u32).to_le_bytes());
+        header.extend_from_slice(file.as_bytes());
+        stream.write_all(&header).map_err(map_err)?;
+        if let Some(key) = key {
+            stream.write_all(key).map_err(map_err)?;
+        }
+        let mut status = [0u8];
+        stream.read_exact(&mut status).map_err(map_err)?;
+        Ok((stream, status[0]))
+    }
+
+    fn expect_ok(&self, op: u8, file: &str, key: Option<&Key>) -> Result<(), Error> {
+        match self.request(op, file, key)?.1 {
+            STATUS_OK => Ok(()),
+            status => Err(Error::Agent(format!("Unexpected response {}", status))),
+        }
+    }
+
+    pub fn get_key(&self, file: &str) -> Result<Option<Key>, Error> {
+        match self.request(OP_GET, file, None)? {
+            (mut stream, STATUS_KEY) => {
+                let mut key = Key::default();
+                stream.read_exact(&mut key).map_err(map_err)?;
+                Ok(Some(key))
+            }
+            (_, STATUS_NOT_FOUND) => Ok(None),
+            (_, status) => Err(Error::Agent(format!("Unexpected response {}", status))),
+        }
+    }
+
+    pub fn put_key(&self, file: &str, key: &Key) -> Result<(), Error> {
+        self.expect_ok(OP_PUT, file, Some(key))
+    }
+
+    pub fn forget(&self, file: &str) -> Result<(), Error> {
+        self.expect_ok(OP_FORGET, file, None)
+    }
+
+    pub fn lock_all(&self) -> Result<(), Error> {
+        self.expect_ok(OP_LOCK_ALL, "", None)
+    }
+}
+
+/// The key of a file if the agent is running and holds it.
+pub fn get_key(file: &str) -> Option<Key> {
+    Client::new(socket_path()).get_key(file).ok().flatten()
+}
+
+/// Hand the key of a file to the agent. Does nothing if it is not running.
+pub fn put_key(file: &str, key: &Key) {
+    let _ = Client::new(socket_path()).put_key(file, key);
+}
+
+pub fn forget(file: &str) -> Result<(), Error> {
+    Client::new(socket_path()).forget(file)
+}
+
+pub fn lock_all() -> Result<(), Error> {
+    Client::new(socket_path()).lock_all()
+}
+
+/// A key kept in its own allocation, locked into memory, and wiped when
+/// dropped.
+struct LockedKey {
+    key: Box<Key>,
+    locked: bool,
+    expires: Instant,
+}
+
+impl LockedKey {
+    /// A zeroed key to read into, so that the key is never anywhere else.
+    /// Failing to lock, e.g., over RLIMIT_MEMLOCK, still leaves the key
+    /// usable; it may just be swapped out.
+    fn new(timeout: Duration) -> Self {
+        let key = Box::new(Key::default());
+        let locked = unsafe { libc::mlock(key.as_ptr().cast(), key.len()) } == 0;
+        Self {
+            key,
+            locked,
+            expires: Instant::now() + timeout,
+        }
+    }
+}
+
+impl Drop for LockedKey {
+    fn drop(&mut self) {
+        for byte in self.key.iter_mut() {
+            unsafe { std::ptr::write_volatile(byte, 0) };
+        }
+        if self.locked {
+            unsafe { libc::munlock(self.key.as_ptr().cast(), self.key.len()) };
+        }
+    }
+}
+
+type Keys = Arc<Mutex<HashMap<String, LockedKey>>>;
+
+/// The agent, listening but not serving yet.
+pub struct Agent {
+    listener: UnixListener,
+    keys: Keys,
+    timeout: Duration,
+}
+
+impl Agent {
+    /// Listen on `path`. Keys are forgotten after `timeout`, counted from
+    /// when they were handed over. A socket left by an agent that did not
+    /// exit cleanly is taken over; one with an agent listening is not.
+    pub fn bind(path: &Path, timeout: Duration) -> Result<Self, Error> {
+        if UnixStream::connect(path).is_ok() {
+            return Err(Error::Agent(format!(
+                "An agent is already listening on {}",
+                path.display()
+            )));
+        }
+        let _ = std::fs::remove_file(path);
+
+        // Create the socket in a directory only the owner can enter and move
+        // it into place once restricted, so that it is never reachable by
+        // others
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let dir = path.parent().unwrap_or(Path::new(".")).join(format!(
+            ".{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&dir)
+            .map_err(map_err)?;
+        let private = dir.join("agent.sock");
+        let listener = UnixListener::bind(&private).and_then(|listener| {
+            std::fs::set_permissions(&private, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::rename(&private, path)?;
+            Ok(listener)
+        });
+        let _ = std::fs::remove_file(&private);
+        let _ = std::fs::remove_dir(&dir);
+        Ok(Self {
+            listener: listener.map_err(map_err)?,
+            keys: Default::default(),
+            timeout,
+        })
+    }
+
+    /// Answer requests until the process is killed, each connection on its
+    /// own thread so that a client that stalls does not hold up the others.
+    /// A request that fails is given to `on_error` and does not stop the
+    /// agent.
+    pub fn serve<F: Fn(Error) + Send + Sync + 'static>(self, on_error: F) {
+        let expiring = self.keys.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(Duration::from_secs(1));
+            let now = Instant::now();
+            expiring.lock().unwrap().retain(|_, key| key.expires > now);
+        });
+
+        let on_error = Arc::new(on_error);
+        for stream in self.listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let keys = self.keys.clone();
+                    let timeout = self.timeout;
+                    let on_error = on_error.clone();
+                    std::thread::spawn(move || {
+                        if let Err(err) = handle(&keys, timeout, stream) {
+                            on_error(err);
+                        }
+                    });
+                }
+                Err(err) => on_error(map_err(err)),
+            }
+        }
+    }
+}
+
+fn handle(keys: &Keys, timeout: Duration, mut stream: UnixStream) -> Result<(), Error> {
+    stream.set_read_timeout(Some(IO_TIMEOUT)).map_err(map_err)?;
+    stream
+        .set_write_timeout(Some(IO_TIMEOUT))
+        .map_err(map_err)?;
+    let mut header = [0u8; 5];
+    stream.read_exact(&mut header).map_err(map_err)?;
+    let len = u32::from_le_bytes(header[1..].try_into().unwrap()) as usize;
+    if len > MAX_FILE_NAME_LEN {
+        return Err(Error::Agent(format!("File name of {} bytes", len)));
+    }
+    let mut file = vec![0u8; len];
+    stream.read_exact(&mut file).map_err(map_err)?;
+    let file = String::from_utf8(file).map_err(|_| Error::InvalidUTF8)?;
+
+    // Read the whole request before taking the keys, which a client that
+    // stalls would otherwise hold on to
+    let mut warning = None;
+    let mut new_key = None;
+    if header[0] == OP_PUT {
+        let mut key = LockedKey::new(timeout);
+        stream.read_exact(&mut key.key[..]).map_err(map_err)?;
+        if !key.locked {
+            warning = Some(Error::Agent(format!(
+                "Failed to lock the key of {} in memory",
+                file
+            )));
+        }
+        new_key = Some(key);
+    }
+
+    let mut keys = keys.lock().unwrap();
+    let now = Instant::now();
+    keys.retain(|_, key| key.expires > now);
+    match header[0] {
+        OP_GET => match keys.get(&file) {
+            Some(key) => {
+                stream.write_all(&[STATUS_KEY]).map_err(map_err)?;
+                stream.write_all(&key.key[..]).map_err(map_err)?;
+            }
+            None => stream.write_all(&[STATUS_NOT_FOUND]).map_err(map_err)?,
+        },
+        OP_PUT => {
+            keys.insert(file, new_key.unwrap());
+            stream.write_all(&[STATUS_OK]).map_err(map_err)?;
+        }
+        OP_FORGET => {
+            keys.remove(&file);
+            stream.write_all(&[STATUS_OK]).map_err(map_err)?;
+        }
+        OP_LOCK_ALL => {
+            keys.clear();
+            stream.write_all(&[STATUS_OK]).map_err(map_err)?;
+        }
+        op => return Err(Error::Agent(format!("Unknown request {}", op))),
+    }
+    warning.map_or(Ok(()), Err)
+}
@@ -1,16 +1,20 @@
 use homedir::my_home;
-use std::path::PathBuf;
 
 use eframe::egui;
 use egui::{Color32, FontDefinitions, Theme};
 
-mod config;
-use config::Config;
+use crate::{config::Config, error::Error};
 
 mod content;
 use content::Content;
 
 mod build;
+use build::file_list::Importing;
+
+pub mod session;
+use session::Session;
+
+pub mod task;
 
 mod macos;
 mod windows;
@@ -21,8 +25,9 @@ pub struct MyApp {
     next_content: Option<Content>,
     file_names: Vec<String>,
     creating_new_file: Option<String>,
-    waiting_for_password_for_safe_note: Option<(PathBuf, String, String)>,
+    importing: Option<Importing>,
     config: Config,
+    session: Session,
 }
 
 impl MyApp {
@@ -48,11 +53,12 @@ impl MyApp {
         // Tell egui to use these fonts:
         cc.egui_ctx.set_fonts(fonts);
 
-        Self {
-            file_names,
+        let mut app = Self {
             config,
             ..Default::default()
-        }
+        };
+        app.set_file_names(file_names);
+        app
     }
 
     fn load_local_font_and_insert(name: &'static str, index: usize, fonts: &mut FontDefinitions) {
@@ -62,7 +68,7 @@ impl MyApp {
         //     .iter()
         //     .for_each(|name| println!("Family: {}", name));
         let font_data = if name == "LXGW" {
-            egui::FontData::from_static(include_bytes!("../assets/LXGWWenKaiGB-Regular.ttf"))
+            egui::FontData::from_static(crate::consts::LXGW_FONT)
         } else {
             panic!("Unknown font name: {}", name);
         };
@@ -88,49 +94,23 @@ impl MyApp {
             .push(name.to_owned());
     }
 
-    fn get_config_and_filenames() -> (Config, Vec<String>) {
-        let config_path = std::env::var("SAFE_WRITING_CONFIG_DIR")
-            .map(|p| std::path::PathBuf::from(p))
-            .unwrap_or(my_home().unwrap().unwrap().as_path().join(".safe_writing"));
-        if !config_path.is_dir() {
-            if config_path.exists() {
-                panic!("Config path {} is not a directory", config_path.display());
-            }
-            std::fs::create_dir_all(&config_path).unwrap();
-        }
-
-        let config_file = config_path.join("config.toml");
-        let config = if !config_file.exists() {
-            let config = Config {
-                font_size: 24.0,
-                data_dir: config_path.to_str().unwrap().to_owned(),
-            };
-            std::fs::write(config_file.clone(), toml::to_string(&config).unwrap()).unwrap();
-            config
-        } else {
-            toml::from_str(&std::fs::read_to_string(config_file).unwrap()).unwrap()
-        };
+    fn get_config_and_filenames() -> (Config, Result<Vec<String>, Error>) {
+        let config =
+            Config::load().unwrap_or_else(|err| panic!("Failed to load config: {:?}", err));
+        let file_names = config.storage().list();
+        (config, file_names)
+    }
 
-        let data_dir = std::path::PathBuf::from(&config.data_dir);
-        if !data_dir.is_dir() {
-            if data_dir.exists() {
-                panic!("Data dir {} is not a directory", data_dir.display());
+    /// Show why the files could not be listed instead of an empty list that
+    /// looks like they are gone.
+    fn set_file_names(&mut self, file_names: Result<Vec<String>, Error>) {
+        match file_names {
+            Ok(file_names) => self.file_names = file_names,
+            Err(err) => {
+                self.file_names = vec![];
+                self.content = Content::Error(format!("Failed to list files: {:?}", err));
             }
-            std::fs::create_dir_all(&data_dir).unwrap();
         }
-        let mut file_names = std::fs::read_dir(&data_dir)
-            .unwrap()
-            .map(|e| e.unwrap().file_name().into_string().unwrap())
-            .filter_map(|filename| {
-                if filename.ends_with(".safe") {
-                    Some(filename[0..filename.len() - 5].to_string())
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-        file_names.sort();
-        (config, file_names)
     }
 
     fn is_dirty(&self) -> bool {
@@ -173,6 +153,16 @@ impl eframe::App for MyApp {
         if let Some(content) = self.next_content.take() {
             self.content = content;
         }
+        if let Content::PlainText(editor_state) = &mut self.content {
+            if let Some(reason) = self.session.poll(ctx, &self.config.auto_lock) {
+                Self::lock(editor_state, Some(reason.message().to_string()));
+                // If the file cannot be saved it stays open. Try again only
+                // after another idle period.
+                self.session.reset();
+            }
+        } else {
+            self.session.reset();
+        }
         egui::CentralPanel::default()
             .frame(egui::Frame::default().fill(Color32::BLACK))
             .show(ctx, |ui| self.main_layout(ctx, ui));
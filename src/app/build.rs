@@ -5,10 +5,13 @@ use egui::{Color32, InnerResponse, RichText, Vec2, WidgetText};
 const PASSWORD_SCREEN_TOP_SPACE: f32 = 200.0;
 const INFO_TEXT_SIZE: f32 = 18.0;
 
+pub(super) mod backup;
 pub(super) mod button_style;
 pub(super) mod editor;
 pub(super) mod file_list;
 pub(super) mod locked;
+pub(super) mod rekey;
+pub(super) mod verify;
 
 impl MyApp {
     pub(super) fn main_layout(
@@ -49,8 +52,35 @@ impl MyApp {
                         },
                     );
                 }
+                Content::Rekey(ref mut rekey_state) => {
+                    ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+                        ui.allocate_space(Vec2::new(0.0, PASSWORD_SCREEN_TOP_SPACE));
+                        Self::build_rekey(rekey_state, ctx, ui);
+                    });
+                }
+                Content::Verify(ref mut verify_state) => {
+                    ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+                        ui.allocate_space(Vec2::new(0.0, PASSWORD_SCREEN_TOP_SPACE));
+                        Self::build_verify(verify_state, ctx, ui);
+                    });
+                }
+                Content::Backup(ref mut backup_state) => {
+                    ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+                        ui.allocate_space(Vec2::new(0.0, PASSWORD_SCREEN_TOP_SPACE));
+                        Self::build_backup(
+                            backup_state,
+                            &mut self.file_names,
+                            &mut self.config,
+                            ctx,
+                            ui,
+                        );
+                    });
+                }
                 Content::PlainText(editor_state) => {
                     Self::build_editor(&mut self.next_content, editor_state, ui);
+                    if let Some(deleted) = editor_state.take_deleted_file() {
+                        self.file_names.retain(|name| name != &deleted);
+                    }
                 }
                 Content::Error(err) => {
                     ui.with_layout(
@@ -0,0 +1,198 @@
+use crate::{
+    app::{task::Task, MyApp},
+    backup::{default_backup_file_name, restore_backup, Backup, OnConflict, BACKUP_EXTENSION},
+    config::Config,
+};
+
+use eframe::egui;
+use egui::{Color32, RichText, TextEdit, Vec2};
+
+/// Back up every file in the data dir with config.toml into one archive, or
+/// restore such an archive, see `backup::Backup`.
+#[derive(Default, Clone)]
+pub struct BackupState {
+    password: String,
+    confirm_password: String,
+    on_conflict: OnConflict,
+    config: Config,
+    /// Lines of the report, and whether they are failures
+    running: Option<Task<Vec<(String, String, bool)>>>,
+    /// Whether the running task restores, which may replace config.toml
+    restoring: bool,
+    report: Vec<(String, String, bool)>,
+    error_message: Option<String>,
+}
+
+impl BackupState {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    fn back_up(&mut self, ctx: &egui::Context) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Backups", &[BACKUP_EXTENSION])
+            .set_file_name(default_backup_file_name())
+            .save_file()
+        else {
+            return;
+        };
+        let storage = self.config.storage();
+        let password = self.password.clone();
+        self.restoring = false;
+        self.running = Some(Task::spawn(ctx, move || {
+            let config = std::fs::read(Config::config_dir().join("config.toml")).ok();
+            let file = path.display().to_string();
+            match Backup::create(storage.as_ref(), config)
+                .and_then(|backup| backup.save(&password, &path).map(|_| backup))
+            {
+                Ok(backup) => vec![(
+                    file,
+                    format!("backed up {} files", backup.files.len()),
+                    false,
+                )],
+                Err(err) => vec![(file, format!("failed: {:?}", err), true)],
+            }
+        }));
+    }
+
+    fn restore(&mut self, ctx: &egui::Context) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Backups", &[BACKUP_EXTENSION])
+            .pick_file()
+        else {
+            return;
+        };
+        let storage = self.config.storage();
+        let password = self.password.clone();
+        let on_conflict = self.on_conflict;
+        self.restoring = true;
+        self.running = Some(Task::spawn(ctx, move || {
+            let backup = match Backup::load(&password, &path) {
+                Ok(backup) => backup,
+                Err(err) => {
+                    return vec![(
+                        path.display().to_string(),
+                        format!("failed: {:?}", err),
+                        true,
+                    )]
+                }
+            };
+            let config_file = Config::config_dir().join("config.toml");
+            restore_backup(storage.as_ref(), &backup, Some(&config_file), on_conflict)
+                .into_iter()
+                .map(|r| (r.file, r.outcome.to_string(), r.outcome.is_failed()))
+                .collect()
+        }));
+    }
+}
+
+impl MyApp {
+    pub(super) fn build_backup(
+        backup_state: &mut BackupState,
+        file_names: &mut Vec<String>,
+        config: &mut Config,
+        ctx: &egui::Context,
+        ui: &mut egui::Ui,
+    ) {
+        let busy = backup_state.running.is_some();
+        ui.label(
+            RichText::from("Back up all files and config.toml into one archive encrypted with this password. The files stay encrypted with their own passwords too.")
+                .size(14.0),
+        );
+        ui.allocate_space(Vec2::new(0.0, 10.0));
+        for (text, hint) in [
+            (&mut backup_state.password, "Backup Password"),
+            (&mut backup_state.confirm_password, "Confirm Password"),
+        ] {
+            ui.add(
+                TextEdit::singleline(text)
+                    .password(true)
+                    .interactive(!busy)
+                    .hint_text(hint),
+            );
+        }
+        ui.allocate_space(Vec2::new(0.0, 10.0));
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+            ui.label(RichText::from("When a name is taken:").size(14.0));
+            for (on_conflict, caption) in [
+                (OnConflict::Skip, "Skip"),
+                (OnConflict::Overwrite, "Overwrite"),
+                (OnConflict::Rename, "Keep Both"),
+            ] {
+                ui.radio_value(&mut backup_state.on_conflict, on_conflict, caption);
+            }
+        });
+        ui.allocate_space(Vec2::new(0.0, 10.0));
+        let has_password = !backup_state.password.is_empty();
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+            if ui.button(RichText::from("Back Up...").size(18.0)).clicked() && !busy {
+                backup_state.error_message = if !has_password {
+                    Some("Enter a password for the backup".to_string())
+                } else if backup_state.password != backup_state.confirm_password {
+                    Some("The passwords do not match".to_string())
+                } else {
+                    None
+                };
+                if backup_state.error_message.is_none() {
+                    backup_state.back_up(ctx);
+                }
+            }
+            // The password of an existing backup needs no confirmation
+            if ui.button(RichText::from("Restore...").size(18.0)).clicked() && !busy {
+                backup_state.error_message =
+                    (!has_password).then(|| "Enter the password of the backup".to_string());
+                if has_password {
+                    backup_state.restore(ctx);
+                }
+            }
+        });
+        if let Some(error_message) = &backup_state.error_message {
+            ui.label(RichText::from(error_message).color(Color32::RED));
+        }
+        if busy {
+            ui.add(egui::Spinner::new().size(18.0));
+        }
+        if let Some(report) = backup_state.running.as_ref().and_then(|task| task.poll()) {
+            backup_state.running = None;
+            backup_state.report = report
+                .unwrap_or_else(|err| vec![("".to_string(), format!("failed: {:?}", err), true)]);
+            // Use the restored settings from now on. The data dir and the
+            // storage are kept, so the restored files are still the ones shown.
+            if backup_state.restoring {
+                match Config::load() {
+                    Ok(loaded) => {
+                        *config = loaded.clone();
+                        backup_state.config = loaded;
+                    }
+                    Err(err) => backup_state.report.push((
+                        "config.toml".to_string(),
+                        format!("failed to reload: {:?}", err),
+                        true,
+                    )),
+                }
+            }
+            if let Ok(names) = backup_state.config.storage().list() {
+                *file_names = names;
+            }
+        }
+        ui.allocate_space(Vec2::new(0.0, 10.0));
+        egui::ScrollArea::vertical()
+            .id_salt("backup_report")
+            .show(ui, |ui| {
+                for (file, outcome, failed) in backup_state.report.iter() {
+                    ui.label(
+                        RichText::from(format!("{}: {}", file, outcome))
+                            .size(14.0)
+                            .color(if *failed {
+                                Color32::RED
+                            } else {
+                                Color32::WHITE
+                            }),
+                    );
+                }
+            });
+    }
+}
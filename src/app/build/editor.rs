@@ -1,8 +1,10 @@
 pub use self::state::EditorState;
 use super::MyApp;
 
+mod export;
 mod file_ops;
 mod images;
+mod merge;
 mod passages;
 mod state;
 mod ui;
@@ -0,0 +1,255 @@
+use super::MyApp;
+
+pub use super::state::EditorState;
+use super::state::ExportSelection;
+
+use crate::app::build::button_style::ButtonStyle;
+use crate::export::{export_epub, export_html, export_markdown, export_pdf};
+use crate::safe_note::{save_safe_note_file, SafeNoteFile};
+
+use eframe::egui;
+use egui::{Color32, FontFamily, FontId, FontSelection, RichText, Vec2};
+
+/// The formats the chosen passages can be exported in, each into one file.
+#[derive(Clone, Copy)]
+enum ExportFormat {
+    Html,
+    Epub,
+    Pdf,
+    /// Encrypted with a password of its own
+    SafeNotes,
+}
+
+impl ExportFormat {
+    const ALL: [ExportFormat; 4] = [
+        ExportFormat::Html,
+        ExportFormat::Epub,
+        ExportFormat::Pdf,
+        ExportFormat::SafeNotes,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Html => "HTML",
+            ExportFormat::Epub => "EPUB",
+            ExportFormat::Pdf => "PDF",
+            ExportFormat::SafeNotes => "SafeNotes",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Html => "html",
+            ExportFormat::Epub => "epub",
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::SafeNotes => "json",
+        }
+    }
+}
+
+impl MyApp {
+    pub(super) fn build_export_markdown_button(editor_state: &mut EditorState, ui: &mut egui::Ui) {
+        if ui
+            .add(Self::make_passage_list_main_button(
+                "Export Markdown",
+                ButtonStyle::NormalInMenu,
+                false,
+            ))
+            .clicked()
+        {
+            if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                // A folder named after the file, next to whatever else is
+                // in the chosen one
+                let dir = path.join(editor_state.filename());
+                editor_state.export_status = Some(
+                    export_markdown(editor_state.plaintext(), &dir)
+                        .map(|_| format!("Exported to {}", dir.display()))
+                        .map_err(|err| format!("Failed to export: {:?}", err)),
+                );
+            }
+        }
+    }
+
+    pub(super) fn build_export_button(editor_state: &mut EditorState, ui: &mut egui::Ui) {
+        if ui
+            .add(Self::make_passage_list_main_button(
+                "Export...",
+                ButtonStyle::NormalInMenu,
+                false,
+            ))
+            .clicked()
+        {
+            editor_state.exporting = match editor_state.exporting {
+                Some(_) => None,
+                None => Some(ExportSelection {
+                    selected: vec![true; editor_state.plaintext().num_passages()],
+                    embed_font: true,
+                    safe_notes_password: String::new(),
+                    safe_notes_confirm_password: String::new(),
+                }),
+            };
+        }
+    }
+
+    /// Choose the passages to export, all of them at first, and the format
+    /// to export them in.
+    pub(super) fn build_export_selection(editor_state: &mut EditorState, ui: &mut egui::Ui) {
+        let num_passages = editor_state.plaintext().num_passages();
+        let Some(ExportSelection {
+            selected,
+            embed_font,
+            safe_notes_password,
+            safe_notes_confirm_password,
+        }) = &mut editor_state.exporting
+        else {
+            return;
+        };
+        // Passages may be added or removed while choosing
+        selected.resize(num_passages, true);
+        let mut export = None;
+        let mut cancel = false;
+        ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+            ui.allocate_space(Vec2::new(0.0, 50.0));
+            ui.label(RichText::from("Choose the passages to export").size(18.0));
+            ui.horizontal(|ui| {
+                if ui.button("All").clicked() {
+                    selected.iter_mut().for_each(|s| *s = true);
+                }
+                if ui.button("None").clicked() {
+                    selected.iter_mut().for_each(|s| *s = false);
+                }
+            });
+            egui::ScrollArea::vertical()
+                .id_salt("export_selection")
+                .max_height(400.0)
+                .show(ui, |ui| {
+                    for (i, passage) in editor_state.plaintext.passages().iter().enumerate() {
+                        ui.checkbox(&mut selected[i], RichText::from(passage.title()).size(18.0));
+                    }
+                });
+            ui.checkbox(
+                embed_font,
+                RichText::from("Embed the font into EPUB").size(18.0),
+            );
+            ui.add(
+                egui::TextEdit::singleline(safe_notes_password)
+                    .font(FontSelection::FontId(FontId::new(
+                        18.0,
+                        FontFamily::Proportional,
+                    )))
+                    .password(true)
+                    .hint_text("Password of SafeNotes"),
+            );
+            ui.add(
+                egui::TextEdit::singleline(safe_notes_confirm_password)
+                    .font(FontSelection::FontId(FontId::new(
+                        18.0,
+                        FontFamily::Proportional,
+                    )))
+                    .password(true)
+                    .hint_text("Confirm Password of SafeNotes"),
+            );
+            let none_selected = !selected.contains(&true);
+            ui.horizontal(|ui| {
+                for format in ExportFormat::ALL {
+                    let disabled = none_selected
+                        || matches!(format, ExportFormat::SafeNotes)
+                            && (safe_notes_password.is_empty()
+                                || safe_notes_password != safe_notes_confirm_password);
+                    if ui
+                        .add(Self::make_control_button(
+                            &format!("Export as {}", format.name()),
+                            ButtonStyle::Normal,
+                            disabled,
+                        ))
+                        .clicked()
+                        && !disabled
+                    {
+                        export = Some(format);
+                    }
+                }
+                if ui
+                    .add(Self::make_control_button(
+                        "Cancel",
+                        ButtonStyle::Normal,
+                        false,
+                    ))
+                    .clicked()
+                {
+                    cancel = true;
+                }
+            });
+        });
+
+        if cancel {
+            editor_state.exporting = None;
+        }
+        if let Some(format) = export {
+            Self::export_selection(editor_state, format);
+        }
+    }
+
+    fn export_selection(editor_state: &mut EditorState, format: ExportFormat) {
+        let Some(selection) = editor_state.exporting.take() else {
+            return;
+        };
+        let indices = (0..selection.selected.len())
+            .filter(|&i| selection.selected[i])
+            .collect::<Vec<_>>();
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter(format.name(), &[format.extension()])
+            .set_file_name(format!(
+                "{}.{}",
+                editor_state.filename(),
+                format.extension()
+            ))
+            .save_file()
+        else {
+            // Keep choosing
+            editor_state.exporting = Some(selection);
+            return;
+        };
+        let plaintext = editor_state.plaintext();
+        let title = editor_state.filename();
+        let result = match format {
+            ExportFormat::Html => export_html(plaintext, title, &indices, &path),
+            ExportFormat::Epub => {
+                export_epub(plaintext, title, &indices, selection.embed_font, &path)
+            }
+            ExportFormat::Pdf => export_pdf(
+                plaintext,
+                title,
+                &indices,
+                editor_state.config().pdf_font_size(),
+                &path,
+            ),
+            ExportFormat::SafeNotes => save_safe_note_file(
+                &selection.safe_notes_password,
+                &SafeNoteFile::from_passages(indices.iter().map(|&i| &plaintext.passages()[i])),
+                &path,
+            ),
+        };
+        editor_state.export_status = Some(
+            result
+                .map(|_| format!("Exported to {}", path.display()))
+                .map_err(|err| format!("Failed to export: {:?}", err)),
+        );
+    }
+
+    pub(super) fn build_export_status(editor_state: &mut EditorState, ui: &mut egui::Ui) {
+        let Some(status) = &editor_state.export_status else {
+            return;
+        };
+        let (message, color) = match status {
+            Ok(message) => (message, Color32::LIGHT_GREEN),
+            Err(message) => (message, Color32::RED),
+        };
+        if ui
+            .add(egui::Label::new(RichText::from(message).color(color)).sense(egui::Sense::click()))
+            .on_hover_text("Click to dismiss")
+            .clicked()
+        {
+            editor_state.export_status = None;
+        }
+    }
+}
@@ -1,6 +1,9 @@
-use super::state::EditorState;
+use super::state::{EditorState, Locking, SaveStatus};
 use super::{super::locked::EncryptedFileState, MyApp};
-use crate::app::content::Content;
+use crate::{
+    app::{content::Content, task::Task},
+    error::Error,
+};
 
 use eframe::egui;
 
@@ -9,60 +12,210 @@ impl MyApp {
         editor_state: &mut EditorState,
         filename: &String,
         password: &String,
-        ui: &mut egui::Ui,
     ) {
         if filename == &editor_state.filename {
-            editor_state.error_appending_another_file = Some("Cannot append to self".to_string());
+            editor_state.error_appending_another_file =
+                Some("Cannot merge into itself".to_string());
             return;
         }
 
-        let path = editor_state.full_path_of(&filename);
-        if !path.exists() {
-            editor_state.error_appending_another_file =
-                Some(format!("File {}.safe not exists", filename));
-        } else {
-            match std::fs::read(path) {
-                Ok(data) => {
-                    let content = String::from_utf8(data).unwrap();
-                    if content.is_empty() {
-                        editor_state.error_appending_another_file =
-                            Some(format!("File {}.safe is empty", filename));
-                    } else {
-                        Self::try_appending_safe_file_content(
-                            editor_state,
-                            filename,
-                            &content,
-                            password,
-                            ui,
-                        );
-                    }
-                }
-                Err(err) => {
+        match editor_state.config().storage().read(filename) {
+            Err(Error::FileNotFound(_)) => {
+                editor_state.error_appending_another_file =
+                    Some(format!("File {}.safe not exists", filename));
+            }
+            Ok(data) => {
+                let content = String::from_utf8(data).unwrap();
+                if content.is_empty() {
                     editor_state.error_appending_another_file =
-                        Some(format!("Failed to read file {}.safe: {}", filename, err));
+                        Some(format!("File {}.safe is empty", filename));
+                } else {
+                    Self::start_merging(editor_state, filename, &content, password);
                 }
             }
+            Err(err) => {
+                editor_state.error_appending_another_file =
+                    Some(format!("Failed to read file {}.safe: {:?}", filename, err));
+            }
         }
     }
-    pub(super) fn save(editor_state: &mut EditorState) {
+
+    /// Start saving a snapshot of the file in the background. If a save is
+    /// already running, another one is queued to run after it, and repeated
+    /// requests in the meantime are coalesced into that one.
+    pub(super) fn save(editor_state: &mut EditorState, ctx: &egui::Context) {
         // EditorState::clean_non_referenced_images(editor_state, ctx);
-        let path = editor_state.full_path();
-        std::fs::write(path, editor_state.plaintext.encrypt(&editor_state.password)).unwrap();
+        if editor_state.saving.is_some() {
+            editor_state.save_queued = true;
+            return;
+        }
+        editor_state.saving = Some(Self::spawn_save(editor_state, ctx));
+        editor_state.save_status = SaveStatus::Saving;
+        // Edits made while saving set the flag again
         editor_state.dirty = false;
     }
 
-    pub(super) fn save_and_lock(
-        next_content: &mut Option<Content>,
+    fn spawn_save(editor_state: &EditorState, ctx: &egui::Context) -> Task<Result<String, Error>> {
+        let config = editor_state.config().clone();
+        let filename = editor_state.filename.clone();
+        let plaintext = editor_state.plaintext.clone();
+        let key = editor_state.key;
+        Task::spawn(ctx, move || {
+            config.storage().save_with_key(&filename, &plaintext, &key)
+        })
+    }
+
+    fn finish_save(editor_state: &mut EditorState, result: Result<String, Error>) {
+        editor_state.saving = None;
+        match result {
+            Ok(ciphertext) => {
+                editor_state.save_status = SaveStatus::Saved;
+                editor_state.saved_ciphertext = Some(ciphertext);
+            }
+            Err(err) => {
+                editor_state.save_status = SaveStatus::Failed(format!("{:?}", err));
+                editor_state.dirty = true;
+            }
+        }
+    }
+
+    /// Called on every frame to pick up the result of the save in the
+    /// background, and start the queued one.
+    pub(super) fn poll_save(editor_state: &mut EditorState, ctx: &egui::Context) {
+        if let Some(result) = editor_state
+            .saving
+            .as_ref()
+            .and_then(|task| task.poll_result())
+        {
+            Self::finish_save(editor_state, result);
+            if editor_state.save_queued {
+                editor_state.save_queued = false;
+                if editor_state.dirty {
+                    Self::save(editor_state, ctx);
+                }
+            }
+        }
+    }
+
+    /// Save the file if it has unsaved changes, then go back to the password
+    /// screen of this file, with `message` if given. Both happen in the
+    /// background, see `poll_lock`.
+    pub(crate) fn lock(editor_state: &mut EditorState, message: Option<String>) {
+        if editor_state.locking.is_some() {
+            return;
+        }
+        // The lock saves whatever a queued save would
+        editor_state.save_queued = false;
+        editor_state.locking = Some(Locking {
+            message,
+            task: None,
+            saving: false,
+        });
+    }
+
+    /// Called on every frame while locking. Waits for a save already running,
+    /// then saves the changes left, if any, or takes the ciphertext last
+    /// written. Returns the password screen once done. If the file cannot be
+    /// saved, it stays open.
+    pub(super) fn poll_lock(
         editor_state: &mut EditorState,
-    ) {
-        let path = editor_state.full_path();
-        let ciphertext = editor_state.plaintext.encrypt(&editor_state.password);
-        std::fs::write(path, &ciphertext).unwrap();
-        editor_state.dirty = false;
-        *next_content = Some(Content::Encrypted(EncryptedFileState::new(
-            editor_state.filename.clone(),
-            ciphertext,
-            editor_state.config().clone(),
-        )));
+        ctx: &egui::Context,
+    ) -> Option<Content> {
+        Self::poll_save(editor_state, ctx);
+        if editor_state.saving.is_some() {
+            return None;
+        }
+        let locking = editor_state.locking.as_ref()?;
+        let Some(task) = &locking.task else {
+            let saving = editor_state.dirty;
+            let task = if saving {
+                editor_state.save_status = SaveStatus::Saving;
+                editor_state.dirty = false;
+                Self::spawn_save(editor_state, ctx)
+            } else if let Some(ciphertext) = editor_state.saved_ciphertext.clone() {
+                Task::spawn(ctx, move || Ok(ciphertext))
+            } else {
+                Self::spawn_read_back(editor_state, ctx)
+            };
+            let locking = editor_state.locking.as_mut().unwrap();
+            locking.task = Some(task);
+            locking.saving = saving;
+            return None;
+        };
+        let result = task.poll_result()?;
+        let locking = editor_state.locking.take().unwrap();
+        match result {
+            Ok(ciphertext) => {
+                let encrypted_file_state = EncryptedFileState::new(
+                    editor_state.filename.clone(),
+                    ciphertext,
+                    editor_state.config().clone(),
+                );
+                Some(Content::Encrypted(match locking.message {
+                    Some(message) => encrypted_file_state.with_message(message),
+                    None => encrypted_file_state,
+                }))
+            }
+            Err(err) => {
+                editor_state.save_status = SaveStatus::Failed(format!("{:?}", err));
+                if locking.saving {
+                    editor_state.dirty = true;
+                }
+                None
+            }
+        }
+    }
+
+    /// Read the ciphertext of a clean file back from the storage. A file
+    /// never saved has none, and its empty content is encrypted instead.
+    fn spawn_read_back(
+        editor_state: &EditorState,
+        ctx: &egui::Context,
+    ) -> Task<Result<String, Error>> {
+        let storage = editor_state.config().storage();
+        let filename = editor_state.filename.clone();
+        let empty = editor_state
+            .plaintext
+            .is_empty()
+            .then(|| editor_state.plaintext.clone());
+        let key = editor_state.key;
+        Task::spawn(ctx, move || {
+            let ciphertext =
+                String::from_utf8(storage.read(&filename)?).map_err(|_| Error::InvalidUTF8)?;
+            match empty {
+                Some(plaintext) if ciphertext.is_empty() => Ok(plaintext.encrypt_with_key(&key)),
+                _ => Ok(ciphertext),
+            }
+        })
+    }
+
+    /// Save the file right away if it has unsaved changes, before deleting
+    /// the file merged into it. This blocks the UI thread.
+    pub(super) fn save_blocking(editor_state: &mut EditorState) -> Result<(), Error> {
+        // Let a save in the background finish first, so that it cannot
+        // overwrite what is written here
+        if let Some(task) = editor_state.saving.take() {
+            Self::finish_save(editor_state, task.wait_result());
+        }
+        editor_state.save_queued = false;
+        if !editor_state.dirty {
+            return Ok(());
+        }
+        match editor_state.config().storage().save_with_key(
+            &editor_state.filename,
+            &editor_state.plaintext,
+            &editor_state.key,
+        ) {
+            Ok(ciphertext) => {
+                editor_state.dirty = false;
+                editor_state.save_status = SaveStatus::Saved;
+                editor_state.saved_ciphertext = Some(ciphertext);
+                Ok(())
+            }
+            Err(err) => {
+                editor_state.save_status = SaveStatus::Failed(format!("{:?}", err));
+                Err(err)
+            }
+        }
     }
 }
@@ -3,12 +3,12 @@ use super::MyApp;
 pub use super::state::EditorState;
 
 use crate::consts::LONG_BUTTON_FONT_SIZE;
-use crate::data_structures::PlainText;
+use crate::data_structures::{self, PlainText};
+use crate::error::Error;
 use crate::{app::build::button_style::ButtonStyle, consts::PASSAGE_LIST_BUTTON_WIDTH};
 
 use eframe::egui;
 use egui::{Color32, FontFamily, FontId, FontSelection, RichText};
-use sha2::Digest;
 
 impl MyApp {
     pub(super) fn build_insert_image_button(editor_state: &mut EditorState, ui: &mut egui::Ui) {
@@ -134,12 +134,7 @@ impl MyApp {
         match PlainText::decrypt(password, content) {
             Ok(appended_plaintext) => {
                 let image = appended_plaintext.images().iter().find_map(|image| {
-                    let digest = format!("{:x}", {
-                        let mut hasher = sha2::Sha256::new();
-                        hasher.update(image);
-                        hasher.finalize()
-                    });
-                    if digest.starts_with(image_digest) {
+                    if data_structures::image_digest(image).starts_with(image_digest) {
                         Some(image)
                     } else {
                         None
@@ -176,13 +171,11 @@ impl MyApp {
             return;
         }
 
-        let path = editor_state.full_path_of(&filename);
-        if !path.exists() {
-            editor_state.error_inserting_safe_image =
-                Some(format!("File {}.safe not exists", filename));
-            return;
-        }
-        match std::fs::read(path) {
+        match editor_state.config().storage().read(filename) {
+            Err(Error::FileNotFound(_)) => {
+                editor_state.error_inserting_safe_image =
+                    Some(format!("File {}.safe not exists", filename));
+            }
             Ok(data) => {
                 let content = String::from_utf8(data).unwrap();
                 if content.is_empty() {
@@ -201,7 +194,7 @@ impl MyApp {
             }
             Err(err) => {
                 editor_state.error_inserting_safe_image =
-                    Some(format!("Failed to read file {}.safe: {}", filename, err));
+                    Some(format!("Failed to read file {}.safe: {:?}", filename, err));
             }
         }
     }
@@ -0,0 +1,198 @@
+use super::MyApp;
+
+pub use super::state::EditorState;
+use super::state::MergeSelection;
+
+use crate::app::build::button_style::ButtonStyle;
+use crate::{
+    data_structures::PlainText,
+    merge::{images_of_passages, match_passages, PassageMatch},
+};
+
+use eframe::egui;
+use egui::{Color32, RichText, Vec2};
+
+impl MyApp {
+    /// Decrypt the other file and let the passages to merge be chosen. The
+    /// ones already here, or nearly, are not chosen at first.
+    pub(super) fn start_merging(
+        editor_state: &mut EditorState,
+        filename: &str,
+        content: &str,
+        password: &str,
+    ) {
+        match PlainText::decrypt(password, content) {
+            Ok(incoming) => {
+                let matches = match_passages(editor_state.plaintext(), &incoming);
+                let selected = matches
+                    .iter()
+                    .map(|m| matches!(m, PassageMatch::New))
+                    .collect();
+                editor_state.merging = Some(MergeSelection {
+                    source: filename.to_string(),
+                    incoming,
+                    matches,
+                    selected,
+                    delete_source: false,
+                });
+                editor_state.exporting = None;
+                editor_state.error_appending_another_file = None;
+            }
+            Err(err) => {
+                editor_state.error_appending_another_file = Some(format!(
+                    "Failed to decrypt file {}.safe: {:?}",
+                    filename, err
+                ));
+            }
+        }
+    }
+
+    pub(super) fn build_merge_selection(editor_state: &mut EditorState, ui: &mut egui::Ui) {
+        let existing = editor_state.plaintext.passages();
+        let Some(MergeSelection {
+            source,
+            incoming,
+            matches,
+            selected,
+            delete_source,
+        }) = &mut editor_state.merging
+        else {
+            return;
+        };
+        let mut merge = false;
+        let mut cancel = false;
+        ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+            ui.allocate_space(Vec2::new(0.0, 50.0));
+            ui.label(
+                RichText::from(format!("Choose the passages to merge from {}", source)).size(18.0),
+            );
+            ui.horizontal(|ui| {
+                if ui.button("All").clicked() {
+                    selected.iter_mut().for_each(|s| *s = true);
+                }
+                if ui.button("None").clicked() {
+                    selected.iter_mut().for_each(|s| *s = false);
+                }
+                if ui.button("Only New").clicked() {
+                    for (s, m) in selected.iter_mut().zip(matches.iter()) {
+                        *s = matches!(m, PassageMatch::New);
+                    }
+                }
+            });
+            egui::ScrollArea::vertical()
+                .id_salt("merge_selection")
+                .max_height(400.0)
+                .show(ui, |ui| {
+                    for (i, passage) in incoming.passages().iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.checkbox(
+                                &mut selected[i],
+                                RichText::from(passage.title()).size(18.0),
+                            );
+                            let title_of = |index: usize| {
+                                existing
+                                    .get(index)
+                                    .map(|p| p.title().as_str())
+                                    .unwrap_or_default()
+                            };
+                            let note = match &matches[i] {
+                                PassageMatch::New => None,
+                                PassageMatch::Identical(index) => {
+                                    Some(format!("identical to \"{}\"", title_of(*index)))
+                                }
+                                PassageMatch::Similar { index, similarity } => Some(format!(
+                                    "{:.0}% like \"{}\"",
+                                    similarity * 100.0,
+                                    title_of(*index)
+                                )),
+                            };
+                            if let Some(note) = note {
+                                ui.label(RichText::from(note).size(14.0).color(Color32::YELLOW));
+                            }
+                        });
+                    }
+                });
+            ui.checkbox(
+                delete_source,
+                RichText::from(format!("Delete {} after merging", source)).size(18.0),
+            );
+            let none_selected = !selected.contains(&true);
+            ui.horizontal(|ui| {
+                if ui
+                    .add(Self::make_control_button(
+                        "Merge",
+                        ButtonStyle::Normal,
+                        none_selected,
+                    ))
+                    .clicked()
+                    && !none_selected
+                {
+                    merge = true;
+                }
+                if ui
+                    .add(Self::make_control_button(
+                        "Cancel",
+                        ButtonStyle::Normal,
+                        false,
+                    ))
+                    .clicked()
+                {
+                    cancel = true;
+                }
+            });
+        });
+
+        if cancel {
+            editor_state.merging = None;
+        }
+        if merge {
+            Self::merge_selection(editor_state, ui.ctx());
+        }
+    }
+
+    /// Append the chosen passages with the images they show, skipping the
+    /// images already here. The other file is only deleted once this one is
+    /// saved with them.
+    fn merge_selection(editor_state: &mut EditorState, ctx: &egui::Context) {
+        let Some(selection) = editor_state.merging.take() else {
+            return;
+        };
+        let indices = (0..selection.selected.len())
+            .filter(|&i| selection.selected[i])
+            .collect::<Vec<_>>();
+        let merged = images_of_passages(&selection.incoming, &indices).and_then(|images| {
+            editor_state
+                .plaintext
+                .append_passages(&selection.incoming, &indices)?;
+            for image in images {
+                EditorState::insert_image(editor_state, image, ctx);
+            }
+            Ok(())
+        });
+        if let Err(err) = merged {
+            editor_state.export_status = Some(Err(format!("Failed to merge: {:?}", err)));
+            return;
+        }
+        editor_state.dirty = true;
+        let merged = format!(
+            "Merged {} passages from {}",
+            indices.len(),
+            selection.source
+        );
+        if !selection.delete_source {
+            editor_state.export_status = Some(Ok(merged));
+            return;
+        }
+        editor_state.export_status = Some(
+            match Self::save_blocking(editor_state)
+                .and_then(|_| editor_state.config().storage().delete(&selection.source))
+            {
+                Ok(()) => {
+                    editor_state.deleted_file = Some(selection.source.clone());
+                    Ok(format!("{} and deleted it", merged))
+                }
+                Err(err) => Err(format!("{}, but failed to delete it: {:?}", merged, err)),
+            },
+        );
+    }
+}
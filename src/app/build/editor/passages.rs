@@ -1,13 +1,13 @@
 use super::MyApp;
 
 pub use super::state::EditorState;
+use super::state::SaveStatus;
 
 use crate::app::build::button_style::ButtonStyle;
 use crate::consts::{
     LONG_BUTTON_FONT_SIZE, PASSAGE_LIST_BUTTON_HEIGHT, PASSAGE_LIST_BUTTON_WIDTH,
     PASSAGE_LIST_WIDTH,
 };
-use crate::{app::content::Content, data_structures::PlainText};
 
 use eframe::egui;
 use egui::{Color32, FontFamily, FontId, FontSelection, Key, RichText, Vec2};
@@ -16,7 +16,6 @@ impl MyApp {
     pub(super) fn build_passage_list_menu_buttons(
         editor_state: &mut EditorState,
         ui: &mut egui::Ui,
-        next_content: &mut Option<Content>,
     ) {
         egui::menu::menu_custom_button(
             ui,
@@ -26,67 +25,40 @@ impl MyApp {
                 Self::build_insert_image_button(editor_state, ui);
                 Self::build_insert_safe_image_button(editor_state, ui);
                 Self::build_clean_nonexist_image_button(editor_state, ui);
-                Self::build_save_lock_button(next_content, editor_state, ui);
+                Self::build_save_lock_button(editor_state, ui);
                 Self::build_rename_button(editor_state, ui);
                 Self::build_delete_button(editor_state, editor_state.selected_index(), ui);
                 Self::build_read_temp_button(editor_state, editor_state.selected_index(), ui);
                 Self::build_append_file_button(editor_state, ui);
+                Self::build_export_markdown_button(editor_state, ui);
+                Self::build_export_button(editor_state, ui);
             },
         );
     }
 
-    pub(super) fn try_appending_safe_file_content(
-        editor_state: &mut EditorState,
-        filename: &str,
-        content: &str,
-        password: &str,
-        ui: &mut egui::Ui,
-    ) {
-        match PlainText::decrypt(password, content) {
-            Ok(appended_plaintext) => {
-                editor_state
-                    .plaintext_mut()
-                    .append_plaintext(&appended_plaintext);
-                appended_plaintext.images().iter().for_each(|image| {
-                    EditorState::insert_image(editor_state, image, ui.ctx());
-                });
-                editor_state.dirty = true;
-                editor_state.appending_another_file = None;
-                editor_state.error_appending_another_file = None;
-            }
-            Err(err) => {
-                editor_state.error_appending_another_file = Some(format!(
-                    "Failed to decrypt file {}.safe: {:?}",
-                    filename, err
-                ));
-            }
-        }
-    }
-
-    pub(super) fn build_passage_list(
-        next_content: &mut Option<Content>,
-        editor_state: &mut EditorState,
-        ui: &mut egui::Ui,
-    ) {
+    pub(super) fn build_passage_list(editor_state: &mut EditorState, ui: &mut egui::Ui) {
         ui.with_layout(egui::Layout::top_down(egui::Align::LEFT), |ui| {
             ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
                 Self::build_add_button(editor_state, ui);
                 Self::build_save_button(editor_state, ui);
                 Self::build_move_button(editor_state, editor_state.selected_index(), true, ui);
                 Self::build_move_button(editor_state, editor_state.selected_index(), false, ui);
-                Self::build_passage_list_menu_buttons(editor_state, ui, next_content);
+                Self::build_passage_list_menu_buttons(editor_state, ui);
             });
+            Self::build_save_status(editor_state, ui);
+            Self::build_export_status(editor_state, ui);
+            Self::poll_save(editor_state, ui.ctx());
             if ui
                 .ctx()
                 .input(|i| i.key_pressed(Key::S) && i.modifiers.command)
             {
-                Self::save(editor_state);
+                Self::save(editor_state, ui.ctx());
             }
             if ui
                 .ctx()
                 .input(|i| i.key_pressed(Key::L) && i.modifiers.command)
             {
-                Self::save_and_lock(next_content, editor_state);
+                Self::lock(editor_state, None);
             }
             egui::ScrollArea::vertical()
                 .id_salt("passage_list")
@@ -139,10 +111,12 @@ impl MyApp {
             );
         }
         if let Some((title, _)) = &editor_state.add_new_passage.clone() {
-            if ui.ctx().input(|i| i.key_pressed(egui::Key::Enter)) {
-                editor_state
+            if ui.ctx().input(|i| i.key_pressed(egui::Key::Enter))
+                && editor_state
                     .plaintext_mut()
-                    .insert_new_passage(current_index, title.clone());
+                    .insert_new_passage(current_index, title.clone())
+                    .is_ok()
+            {
                 editor_state.add_new_passage = None;
                 editor_state.dirty = true;
             }
@@ -202,11 +176,14 @@ impl MyApp {
         if ui.ctx().input(|i| i.key_pressed(egui::Key::Enter)) {
             let selected_index = editor_state.selected_index;
             let title = editor_state.editing_passage_name.clone().unwrap().0;
-            editor_state
+            if editor_state
                 .plaintext_mut()
-                .set_title(selected_index, title);
+                .set_title(selected_index, title)
+                .is_ok()
+            {
+                editor_state.dirty = true;
+            }
             editor_state.editing_passage_name = None;
-            editor_state.dirty = true;
         }
     }
 
@@ -241,15 +218,31 @@ impl MyApp {
             .clicked()
             && editor_state.dirty
         {
-            Self::save(editor_state);
+            Self::save(editor_state, ui.ctx());
         }
     }
 
-    fn build_save_lock_button(
-        next_content: &mut Option<Content>,
-        editor_state: &mut EditorState,
-        ui: &mut egui::Ui,
-    ) {
+    fn build_save_status(editor_state: &EditorState, ui: &mut egui::Ui) {
+        match &editor_state.save_status {
+            SaveStatus::None => {}
+            SaveStatus::Saving => {
+                ui.horizontal(|ui| {
+                    ui.add(egui::Spinner::new().color(Color32::WHITE));
+                    ui.label(RichText::from("Saving").color(Color32::WHITE));
+                });
+            }
+            SaveStatus::Saved => {
+                if !editor_state.dirty {
+                    ui.label(RichText::from("Saved").color(Color32::LIGHT_GREEN));
+                }
+            }
+            SaveStatus::Failed(err) => {
+                ui.label(RichText::from(format!("Failed to save: {}", err)).color(Color32::RED));
+            }
+        }
+    }
+
+    fn build_save_lock_button(editor_state: &mut EditorState, ui: &mut egui::Ui) {
         if ui
             .add(Self::make_passage_list_main_button(
                 "Save & Lock",
@@ -258,7 +251,7 @@ impl MyApp {
             ))
             .clicked()
         {
-            Self::save_and_lock(next_content, editor_state);
+            Self::lock(editor_state, None);
         }
     }
 
@@ -283,11 +276,11 @@ impl MyApp {
             let plaintext = editor_state.plaintext_mut();
 
             if up && selected_index > 0 {
-                plaintext.swap(selected_index, selected_index - 1);
-                editor_state.dirty = true;
-                editor_state.decrease_selected_index();
-            } else if !up && selected_index < plaintext.num_passages() - 1 {
-                plaintext.swap(selected_index, selected_index + 1);
+                if plaintext.swap(selected_index, selected_index - 1).is_ok() {
+                    editor_state.dirty = true;
+                    editor_state.decrease_selected_index();
+                }
+            } else if !up && plaintext.swap(selected_index, selected_index + 1).is_ok() {
                 editor_state.dirty = true;
                 editor_state.increase_selected_index();
             }
@@ -346,15 +339,16 @@ impl MyApp {
             && !editor_state.preview_mode
         {
             let temp_file_path = editor_state.temp_path();
-            if let Ok(temp_content) = std::fs::read_to_string(&temp_file_path) {
-                let plaintext = editor_state.plaintext_mut();
-                plaintext.set_content(
+            let content = editor_state
+                .plaintext()
+                .content_of_passage(selected_index)
+                .cloned();
+            if let (Ok(temp_content), Some(content)) =
+                (std::fs::read_to_string(&temp_file_path), content)
+            {
+                let _ = editor_state.plaintext_mut().set_content(
                     selected_index,
-                    plaintext
-                        .content_of_passage(selected_index)
-                        .unwrap()
-                        .to_string()
-                        + &format!("\n\n{}", temp_content.trim()),
+                    content + &format!("\n\n{}", temp_content.trim()),
                 );
                 editor_state.dirty = true;
                 if let Err(err) = std::fs::remove_file(&temp_file_path) {
@@ -367,7 +361,7 @@ impl MyApp {
     fn build_append_file_button(editor_state: &mut EditorState, ui: &mut egui::Ui) {
         if ui
             .add(Self::make_passage_list_main_button(
-                "Append File",
+                "Merge File",
                 ButtonStyle::NormalInMenu,
                 false,
             ))
@@ -403,8 +397,11 @@ impl MyApp {
         }
         if let Some((filename, password)) = editor_state.appending_another_file.clone() {
             if ui.ctx().input(|i| i.key_pressed(egui::Key::Enter)) && !filename.is_empty() {
-                Self::try_appending_safe_file(editor_state, &filename, &password, ui);
-                editor_state.appending_another_file = None;
+                Self::try_appending_safe_file(editor_state, &filename, &password);
+                // Keep the form open to show what went wrong
+                if editor_state.merging.is_some() {
+                    editor_state.appending_another_file = None;
+                }
             }
             if let Some(error) = &editor_state.error_appending_another_file {
                 ui.add(egui::Label::new(egui::WidgetText::RichText(
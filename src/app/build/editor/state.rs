@@ -1,4 +1,11 @@
-use crate::{app::config::Config, data_structures::PlainText};
+use crate::{
+    app::task::Task,
+    cipher::Key,
+    config::Config,
+    data_structures::{image_digest, image_placeholder, PlainText},
+    error::Error,
+    merge::PassageMatch,
+};
 use std::{
     collections::{HashMap, HashSet},
     path::PathBuf,
@@ -7,7 +14,6 @@ use std::{
 use eframe::egui;
 use egui::{TextureHandle, TextureOptions};
 use image::load_from_memory;
-use sha2::Digest;
 
 #[derive(Default, Clone)]
 pub struct EditorState {
@@ -23,20 +29,78 @@ pub struct EditorState {
     pub(super) appending_another_file: Option<(String, String)>,
     pub(super) error_appending_another_file: Option<String>,
     pub(super) preview_mode: bool,
-    pub(super) password: String,
+    /// Derived from the password; the password itself is not kept
+    pub(super) key: Key,
     pub(super) config: Config,
     pub(super) text_to_insert: Option<String>,
     pub(super) image_to_insert: Option<Vec<u8>>,
     pub(super) inserting_safe_image: Option<(String, String, String)>,
     pub(super) error_inserting_safe_image: Option<String>,
+    pub(super) exporting: Option<ExportSelection>,
+    pub(super) merging: Option<MergeSelection>,
+    /// A file deleted from here, for the file list to drop
+    pub(super) deleted_file: Option<String>,
+    /// Where the file was exported to, or why it failed. Also shows what
+    /// an import left out and how a merge went
+    pub(super) export_status: Option<Result<String, String>>,
     pub(super) show_png_meta_data: Option<usize>,
+    /// Each save gives the ciphertext it wrote
+    pub(super) saving: Option<Task<Result<String, Error>>>,
+    pub(super) save_queued: bool,
+    pub(super) save_status: SaveStatus,
+    /// What the storage holds, as last read or written from here, so that a
+    /// clean file is locked without encrypting it again
+    pub(super) saved_ciphertext: Option<String>,
+    pub(super) locking: Option<Locking>,
+}
+
+/// Going back to the password screen, once the file is saved.
+#[derive(Clone)]
+pub(super) struct Locking {
+    /// Shown on the password screen, e.g., why the file was locked
+    pub(super) message: Option<String>,
+    /// Started once a save already running is done
+    pub(super) task: Option<Task<Result<String, Error>>>,
+    /// Whether the task saves changes, which are unsaved again if it fails
+    pub(super) saving: bool,
+}
+
+/// What is chosen to export, while choosing.
+#[derive(Clone)]
+pub(super) struct ExportSelection {
+    /// Whether each passage is chosen
+    pub(super) selected: Vec<bool>,
+    pub(super) embed_font: bool,
+    pub(super) safe_notes_password: String,
+    pub(super) safe_notes_confirm_password: String,
+}
+
+/// The passages of another file chosen to merge into this one, while
+/// choosing.
+#[derive(Clone)]
+pub(super) struct MergeSelection {
+    pub(super) source: String,
+    pub(super) incoming: PlainText,
+    /// How each incoming passage compares to the passages here
+    pub(super) matches: Vec<PassageMatch>,
+    pub(super) selected: Vec<bool>,
+    pub(super) delete_source: bool,
+}
+
+#[derive(Default, Clone, Debug)]
+pub(super) enum SaveStatus {
+    #[default]
+    None,
+    Saving,
+    Saved,
+    Failed(String),
 }
 
 impl EditorState {
     pub fn new(
         filename: String,
-        plaintext: PlainText,
-        password: String,
+        mut plaintext: PlainText,
+        key: Key,
         config: Config,
         ctx: &egui::Context,
     ) -> Self {
@@ -45,11 +109,13 @@ impl EditorState {
         } else {
             HashMap::default()
         };
+        // Drained every frame, see `build_editor`
+        plaintext.record_changes();
         EditorState {
             filename,
             plaintext,
             image_map,
-            password,
+            key,
             selected_index: 0,
             config,
             ..Default::default()
@@ -83,11 +149,7 @@ impl EditorState {
         let mut image_map = HashMap::default();
         // Compute the SHA256 of this image as the key
         for (i, image) in images.iter().enumerate() {
-            let digest = format!("{:x}", {
-                let mut hasher = sha2::Sha256::new();
-                hasher.update(image);
-                hasher.finalize()
-            });
+            let digest = image_digest(image);
             let handle = Self::load_texture_from_memory(image.as_slice(), ctx);
             if let Ok(handle) = handle {
                 image_map.insert(digest, (i, handle));
@@ -104,11 +166,7 @@ impl EditorState {
         image: &Vec<u8>,
         ctx: &egui::Context,
     ) -> String {
-        let image_digest = format!("{:x}", {
-            let mut hasher = sha2::Sha256::new();
-            hasher.update(image);
-            hasher.finalize()
-        });
+        let image_digest = image_digest(image);
         if editor_state.image_map.contains_key(&image_digest) {
             return image_digest;
         }
@@ -126,22 +184,8 @@ impl EditorState {
         image_digest
     }
 
-    fn image_placeholder(digest: &str) -> String {
-        format!("image!({})", digest)
-    }
-
     pub(super) fn clean_non_referenced_images(editor_state: &mut EditorState, ctx: &egui::Context) {
-        // Collect all the strings of the form image!(...) in the passages
-        let mut image_references = HashSet::new();
-        for passage in editor_state.plaintext.passages() {
-            for line in passage.content().split('\n') {
-                if line.starts_with("image!(") && line.ends_with(")") && line.len() == 72 {
-                    // SHA256 digest hex has length 64, so the line length is
-                    // 64 + len("image!()") = 72
-                    image_references.insert(&line[7..71]);
-                }
-            }
-        }
+        let image_references = editor_state.plaintext.image_references();
 
         let existing_indices = editor_state
             .image_map
@@ -168,30 +212,45 @@ impl EditorState {
 
     pub fn insert_image_at_cursor(&mut self, image: Vec<u8>, ctx: &egui::Context) {
         let digest = Self::insert_image(self, &image, ctx);
-        self.text_to_insert = Some(format!("\n{}\n", Self::image_placeholder(&digest)));
+        self.text_to_insert = Some(format!("\n{}\n", image_placeholder(&digest)));
     }
 
-    pub fn empty(filename: String, password: String, config: Config) -> Self {
+    pub fn empty(filename: String, key: Key, config: Config) -> Self {
+        let mut plaintext = PlainText::empty();
+        plaintext.record_changes();
         EditorState {
             filename,
-            plaintext: PlainText::empty(),
-            password,
+            plaintext,
+            key,
             selected_index: 0,
             config,
             ..Default::default()
         }
     }
 
-    pub fn filename(&self) -> &String {
-        &self.filename
+    /// The ciphertext the file was just decrypted from.
+    pub fn with_saved_ciphertext(self, ciphertext: String) -> Self {
+        Self {
+            saved_ciphertext: Some(ciphertext),
+            ..self
+        }
+    }
+
+    /// Show a problem that does not stop the file from being opened.
+    pub fn with_warning(self, warning: String) -> Self {
+        Self {
+            export_status: Some(Err(warning)),
+            ..self
+        }
     }
 
-    pub fn full_path(&self) -> PathBuf {
-        self.full_path_of(&self.filename)
+    pub fn filename(&self) -> &String {
+        &self.filename
     }
 
-    pub fn full_path_of(&self, filename: &str) -> PathBuf {
-        PathBuf::from(self.data_dir().clone()).join(format!("{}.safe", filename))
+    /// The file deleted since the last call, if any.
+    pub fn take_deleted_file(&mut self) -> Option<String> {
+        self.deleted_file.take()
     }
 
     pub fn temp_path(&self) -> PathBuf {
@@ -227,8 +286,10 @@ impl EditorState {
         }
     }
 
+    /// A save still running in the background counts as unsaved changes, as
+    /// it may fail.
     pub fn is_dirty(&self) -> bool {
-        self.dirty
+        self.dirty || self.saving.is_some()
     }
 
     pub fn config(&self) -> &Config {
@@ -90,13 +90,32 @@ impl MyApp {
         editor_state: &mut EditorState,
         ui: &mut egui::Ui,
     ) {
+        // Nothing can be edited while locking, as it saves a snapshot
+        if editor_state.locking.is_some() {
+            if let Some(content) = Self::poll_lock(editor_state, ui.ctx()) {
+                *next_content = Some(content);
+            }
+            ui.with_layout(
+                egui::Layout::centered_and_justified(egui::Direction::TopDown),
+                |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(egui::Spinner::new().size(18.0).color(Color32::WHITE));
+                        ui.label(RichText::from("Locking").size(18.0).color(Color32::WHITE));
+                    });
+                },
+            );
+            return;
+        }
         egui::Frame::new()
             .fill(Color32::LIGHT_GRAY.gamma_multiply(0.1))
             .inner_margin(5.0)
             .show(ui, |ui| {
-                Self::build_passage_list(next_content, editor_state, ui);
+                Self::build_passage_list(editor_state, ui);
             });
-        if editor_state.plaintext().is_empty() {
+        // Merging into an empty file is fine
+        if editor_state.merging.is_some() {
+            Self::build_merge_selection(editor_state, ui);
+        } else if editor_state.plaintext().is_empty() {
             ui.with_layout(
                 egui::Layout::centered_and_justified(egui::Direction::TopDown),
                 |ui| {
@@ -105,6 +124,8 @@ impl MyApp {
                     )));
                 },
             );
+        } else if editor_state.exporting.is_some() {
+            Self::build_export_selection(editor_state, ui);
         } else if let Some(to_delete_passage_index) = editor_state.confirm_delete_passage {
             ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
                 ui.allocate_space(Vec2::new(0.0, 200.0));
@@ -127,17 +148,24 @@ impl MyApp {
                     .clicked()
                 {
                     let plaintext = editor_state.plaintext_mut();
-                    plaintext.remove_passage(to_delete_passage_index);
-                    let num_passages = plaintext.num_passages();
-                    let new_selected_index = if plaintext.is_empty() {
-                        0
-                    } else if editor_state.selected_index() >= num_passages {
-                        editor_state.selected_index() - 1
-                    } else {
-                        editor_state.selected_index()
-                    };
-                    editor_state.dirty = true;
-                    editor_state.selected_index = new_selected_index;
+                    match plaintext.remove_passage(to_delete_passage_index) {
+                        Ok(_) => {
+                            let num_passages = plaintext.num_passages();
+                            let new_selected_index = if plaintext.is_empty() {
+                                0
+                            } else if editor_state.selected_index() >= num_passages {
+                                editor_state.selected_index() - 1
+                            } else {
+                                editor_state.selected_index()
+                            };
+                            editor_state.dirty = true;
+                            editor_state.selected_index = new_selected_index;
+                        }
+                        Err(err) => {
+                            editor_state.export_status =
+                                Some(Err(format!("Failed to delete passage: {:?}", err)));
+                        }
+                    }
                     editor_state.confirm_delete_passage = None;
                 }
             });
@@ -223,6 +251,11 @@ impl MyApp {
                         });
                 });
         }
+        // Edits through the PlainText API are reported as changes; drain them
+        // here so that none is missed when setting the dirty flag.
+        if !editor_state.plaintext.take_changes().is_empty() {
+            editor_state.dirty = true;
+        }
     }
 
     fn build_editing_area(
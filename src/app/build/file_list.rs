@@ -1,12 +1,18 @@
 use super::{
+    backup::BackupState,
     editor::EditorState,
     locked::{EncryptedFileState, NewFileState},
+    rekey::RekeyState,
+    verify::VerifyState,
     MyApp,
 };
 use crate::{
     app::content::Content,
+    cipher::key_derive,
     consts::{FILE_LIST_BUTTON_WIDTH, FILE_LIST_WIDTH},
+    data_structures::PlainText,
     error::Error,
+    import::{import_day_one, import_enex, import_markdown},
     safe_note::load_safe_note_file,
 };
 use std::{ffi::OsStr, path::PathBuf};
@@ -14,6 +20,83 @@ use std::{ffi::OsStr, path::PathBuf};
 use eframe::egui;
 use egui::{Color32, FontFamily, FontId, FontSelection, Key, RichText, TextEdit, Vec2};
 
+/// Where a new file can be imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ImportKind {
+    SafeNotes,
+    Evernote,
+    DayOne,
+    MarkdownFolder,
+}
+
+impl ImportKind {
+    const ALL: [ImportKind; 4] = [
+        ImportKind::SafeNotes,
+        ImportKind::Evernote,
+        ImportKind::DayOne,
+        ImportKind::MarkdownFolder,
+    ];
+
+    fn caption(&self) -> &'static str {
+        match self {
+            ImportKind::SafeNotes => "Load Safe Notes File",
+            ImportKind::Evernote => "Import Evernote ENEX",
+            ImportKind::DayOne => "Import Day One Export",
+            ImportKind::MarkdownFolder => "Import Markdown Folder",
+        }
+    }
+
+    fn pick(&self) -> Option<PathBuf> {
+        match self {
+            ImportKind::SafeNotes => rfd::FileDialog::new()
+                .add_filter("JSON Files", &["json"])
+                .pick_file(),
+            ImportKind::Evernote => rfd::FileDialog::new()
+                .add_filter("Evernote Exports", &["enex"])
+                .pick_file(),
+            ImportKind::DayOne => rfd::FileDialog::new()
+                .add_filter("Day One Exports", &["zip"])
+                .pick_file(),
+            ImportKind::MarkdownFolder => rfd::FileDialog::new().pick_folder(),
+        }
+    }
+
+    /// The password is the one of the new file, and for SafeNotes also the
+    /// one of the export. Also returns what is left out, if anything.
+    fn load(&self, path: &PathBuf, password: &str) -> Result<(PlainText, Option<String>), Error> {
+        match self {
+            ImportKind::SafeNotes => {
+                let import = load_safe_note_file(password, path)?;
+                let skipped = (!import.errors.is_empty()).then(|| {
+                    format!(
+                        "Skipped {} records: {}",
+                        import.errors.len(),
+                        import
+                            .errors
+                            .iter()
+                            .map(|err| err.to_string())
+                            .collect::<Vec<_>>()
+                            .join("; ")
+                    )
+                });
+                Ok((import.file.into_plaintext(), skipped))
+            }
+            ImportKind::Evernote => Ok((import_enex(path)?, None)),
+            ImportKind::DayOne => Ok((import_day_one(path)?, None)),
+            ImportKind::MarkdownFolder => Ok((import_markdown(path)?, None)),
+        }
+    }
+}
+
+/// An import waiting for the name and the password of the new file.
+pub(crate) struct Importing {
+    kind: ImportKind,
+    path: PathBuf,
+    new_file_name: String,
+    password: String,
+    confirm_password: String,
+}
+
 impl MyApp {
     fn build_create_new_file_button(&mut self, ui: &mut egui::Ui) {
         if ui
@@ -32,109 +115,161 @@ impl MyApp {
         }
     }
 
+    /// A button in the "..." menu, disabled while the opened file has
+    /// unsaved changes. Returns whether it is clicked.
+    fn build_file_list_menu_item(&self, caption: &str, ui: &mut egui::Ui) -> bool {
+        ui.add(
+            egui::Button::new(egui::WidgetText::RichText(
+                RichText::from(caption)
+                    .size(18.0)
+                    .color(if self.is_dirty() {
+                        Color32::GRAY.gamma_multiply(0.2)
+                    } else {
+                        Color32::BLACK
+                    }),
+            ))
+            .min_size(Vec2::new(24.0, 24.0))
+            .fill(Color32::WHITE),
+        )
+        .clicked()
+            && !self.is_dirty()
+    }
+
     fn build_file_list_menu_button(&mut self, ui: &mut egui::Ui) {
-        let data_dir = self.data_dir().clone();
         egui::menu::menu_custom_button(
             ui,
             Self::make_file_list_top_button("...", self.is_dirty()),
             |ui| {
-                if ui
-                    .add(
-                        egui::Button::new(egui::WidgetText::RichText(
-                            RichText::from("Load Safe Notes File").size(18.0).color(
-                                if self.is_dirty() {
-                                    Color32::GRAY.gamma_multiply(0.2)
-                                } else {
-                                    Color32::BLACK
-                                },
-                            ),
-                        ))
-                        .min_size(Vec2::new(24.0, 24.0))
-                        .fill(Color32::WHITE),
-                    )
-                    .clicked()
-                    && !self.is_dirty()
-                {
-                    if self.waiting_for_password_for_safe_note.is_none() {
-                        if let Some(path) = rfd::FileDialog::new()
-                            .add_filter("JSON Files", &["json"])
-                            .pick_file()
-                        {
+                for kind in ImportKind::ALL {
+                    if self.build_file_list_menu_item(kind.caption(), ui) {
+                        if self.importing.as_ref().map(|i| i.kind) == Some(kind) {
+                            self.importing = None;
+                        } else if let Some(path) = kind.pick() {
                             let default_name = path
                                 .file_stem()
                                 .unwrap_or(OsStr::new(""))
                                 .to_string_lossy()
                                 .to_string();
-                            self.waiting_for_password_for_safe_note =
-                                Some((path, default_name, "".to_string()));
+                            self.importing = Some(Importing {
+                                kind,
+                                path,
+                                new_file_name: default_name,
+                                password: "".to_string(),
+                                confirm_password: "".to_string(),
+                            });
                         }
-                    } else {
-                        self.waiting_for_password_for_safe_note = None;
+                    }
+                    if self.importing.as_ref().map(|i| i.kind) == Some(kind) {
+                        self.build_import_form(ui);
                     }
                 }
 
-                if let Some((path, ref mut new_file_name, ref mut password)) =
-                    &mut self.waiting_for_password_for_safe_note
-                {
-                    ui.add(
-                        TextEdit::singleline(new_file_name)
-                            .desired_width(FILE_LIST_BUTTON_WIDTH)
-                            .font(FontSelection::FontId(FontId::new(
-                                18.0,
-                                FontFamily::Proportional,
-                            )))
-                            .hint_text("New Name"),
-                    );
-                    ui.add(
-                        TextEdit::singleline(password)
-                            .desired_width(FILE_LIST_BUTTON_WIDTH)
-                            .font(FontSelection::FontId(FontId::new(
-                                18.0,
-                                FontFamily::Proportional,
-                            )))
-                            .hint_text("Password")
-                            .password(true),
-                    );
-                    if ui.ctx().input(|i| i.key_pressed(Key::Enter)) && !new_file_name.is_empty() {
-                        match load_safe_note_file(password, &path) {
-                            Ok(safe_note) => {
-                                let plaintext = safe_note.into_plaintext();
-                                if self.file_names.contains(new_file_name) {
-                                    self.content = Content::Error(format!(
-                                        "File with name {} already exists",
-                                        new_file_name
-                                    ));
-                                } else {
-                                    let content = plaintext.encrypt(password);
-                                    let path = PathBuf::from(&data_dir)
-                                        .join(format!("{}.safe", new_file_name));
-                                    if std::fs::write(path, content).is_ok() {
-                                        self.file_names.push(new_file_name.clone());
-                                        self.file_names.sort();
-                                        self.content = Content::PlainText(EditorState::new(
-                                            new_file_name.clone(),
-                                            plaintext.clone(),
-                                            password.clone(),
-                                            self.config.clone(),
-                                            &ui.ctx(),
-                                        ));
-                                    }
-                                }
-                            }
-                            Err(err) => {
-                                self.content = Content::Error(format!(
-                                    "Error loading safenote file: {:?}",
-                                    err
-                                ));
-                            }
-                        }
-                        self.waiting_for_password_for_safe_note = None;
-                    }
+                if self.build_file_list_menu_item("Re-encrypt All Files", ui) {
+                    self.content = Content::Rekey(RekeyState::new(self.config.clone()));
+                    ui.close_menu();
+                }
+
+                if self.build_file_list_menu_item("Verify All Files", ui) {
+                    self.content = Content::Verify(VerifyState::new(self.config.clone()));
+                    ui.close_menu();
+                }
+
+                if self.build_file_list_menu_item("Back Up or Restore All Files", ui) {
+                    self.content = Content::Backup(BackupState::new(self.config.clone()));
+                    ui.close_menu();
                 }
             },
         );
     }
 
+    /// Ask for the name of the new file and its password twice, and import
+    /// into it on Enter once the two match.
+    fn build_import_form(&mut self, ui: &mut egui::Ui) {
+        let Some(importing) = &mut self.importing else {
+            return;
+        };
+        ui.add(
+            TextEdit::singleline(&mut importing.new_file_name)
+                .desired_width(FILE_LIST_BUTTON_WIDTH)
+                .font(FontSelection::FontId(FontId::new(
+                    18.0,
+                    FontFamily::Proportional,
+                )))
+                .hint_text("New Name"),
+        );
+        ui.add(
+            TextEdit::singleline(&mut importing.password)
+                .desired_width(FILE_LIST_BUTTON_WIDTH)
+                .font(FontSelection::FontId(FontId::new(
+                    18.0,
+                    FontFamily::Proportional,
+                )))
+                .hint_text("Password")
+                .password(true),
+        );
+        ui.add(
+            TextEdit::singleline(&mut importing.confirm_password)
+                .desired_width(FILE_LIST_BUTTON_WIDTH)
+                .font(FontSelection::FontId(FontId::new(
+                    18.0,
+                    FontFamily::Proportional,
+                )))
+                .hint_text("Confirm Password")
+                .password(true),
+        );
+        if !ui.ctx().input(|i| i.key_pressed(Key::Enter))
+            || importing.new_file_name.is_empty()
+            || importing.password.is_empty()
+            || importing.password != importing.confirm_password
+        {
+            return;
+        }
+        let Importing {
+            kind,
+            path,
+            new_file_name,
+            password,
+            ..
+        } = self.importing.take().unwrap();
+        match kind.load(&path, &password) {
+            Ok((plaintext, skipped)) => {
+                if self.file_names.contains(&new_file_name) {
+                    self.content =
+                        Content::Error(format!("File with name {} already exists", new_file_name));
+                } else {
+                    let key = key_derive(&password);
+                    let content = plaintext.encrypt_with_key(&key);
+                    if let Err(err) = self
+                        .config
+                        .storage()
+                        .write(&new_file_name, content.as_bytes())
+                    {
+                        self.content =
+                            Content::Error(format!("Failed to write {}: {:?}", new_file_name, err));
+                    } else {
+                        self.file_names.push(new_file_name.clone());
+                        self.file_names.sort();
+                        let editor_state = EditorState::new(
+                            new_file_name,
+                            plaintext,
+                            key,
+                            self.config.clone(),
+                            ui.ctx(),
+                        );
+                        self.content = Content::PlainText(match skipped {
+                            Some(skipped) => editor_state.with_warning(skipped),
+                            None => editor_state,
+                        });
+                    }
+                }
+            }
+            Err(err) => {
+                self.content =
+                    Content::Error(format!("Error importing {}: {:?}", path.display(), err));
+            }
+        }
+    }
+
     fn build_refresh_button(&mut self, ui: &mut egui::Ui) {
         if ui
             .add(Self::make_file_list_top_button(
@@ -145,7 +280,7 @@ impl MyApp {
             && !self.is_dirty()
         {
             let (_, file_names) = Self::get_config_and_filenames();
-            self.file_names = file_names;
+            self.set_file_names(file_names);
         }
     }
 
@@ -175,8 +310,7 @@ impl MyApp {
             .clicked()
             && !disabled
         {
-            let path = PathBuf::from(self.data_dir().clone()).join(format!("{}.safe", file_name));
-            let content = std::fs::read(path).map_err(|err| {
+            let content = self.config.storage().read(&file_name).map_err(|err| {
                 Error::FailedToOpenFile(format!("Failed to open file {}: {:?}", file_name, err))
             })?;
 
@@ -184,18 +318,15 @@ impl MyApp {
             if content.is_empty() {
                 self.content = Content::NewFile(NewFileState::new(file_name, self.config.clone()));
             } else {
-                self.content = Content::Encrypted(EncryptedFileState::new(
-                    file_name,
-                    content,
-                    self.config.clone(),
-                ));
+                self.content = Content::Encrypted(
+                    EncryptedFileState::new(file_name, content, self.config.clone()).with_agent(),
+                );
             }
         }
         Ok(())
     }
 
     pub(super) fn build_file_list(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
-        let data_dir = self.data_dir().clone();
         egui::Frame::new()
             .fill(Color32::GRAY.gamma_multiply(0.2))
             .inner_margin(5.0)
@@ -217,14 +348,16 @@ impl MyApp {
                                 .desired_width(FILE_LIST_BUTTON_WIDTH),
                         );
                         if ctx.input(|i| i.key_pressed(egui::Key::Enter)) {
-                            let path = PathBuf::from(&data_dir).join(format!("{}.safe", filename));
-                            if path.exists() {
-                                self.content =
-                                    Content::Error(format!("File {} already exists", filename));
-                            } else if filename.is_empty() {
+                            if filename.is_empty() {
                                 // Do nothing when input none, i.e., just cancel
+                            } else if let Err(err) = self.config.storage().create(filename) {
+                                self.content = Content::Error(match err {
+                                    Error::FileExists(_) => {
+                                        format!("File {} already exists", filename)
+                                    }
+                                    err => format!("Failed to create {}: {:?}", filename, err),
+                                });
                             } else {
-                                std::fs::write(path, "").unwrap();
                                 self.file_names.push(filename.clone());
                                 self.file_names.sort();
                                 self.content = Content::NewFile(NewFileState::new(
@@ -1,8 +1,10 @@
 use crate::{
-    app::{config::Config, content::Content, MyApp},
+    app::{content::Content, task::Task, MyApp},
+    cipher::{key_derive, Key},
+    config::Config,
     data_structures::PlainText,
+    error::Error,
 };
-use std::path::PathBuf;
 
 use super::editor::EditorState;
 use eframe::egui;
@@ -54,6 +56,10 @@ pub struct EncryptedFileState {
     error_message: Option<String>,
     change_password_show: bool,
     config: Config,
+    decrypting: Option<Task<Result<(PlainText, Key), Error>>>,
+    changing_password: Option<Task<Result<(), Error>>>,
+    /// Ask the agent for the key before showing the password field
+    try_agent: bool,
 }
 
 impl EncryptedFileState {
@@ -66,12 +72,26 @@ impl EncryptedFileState {
         }
     }
 
-    pub fn filename(&self) -> &String {
-        &self.filename
+    /// Unlock with the key held by the agent if there is one. Not done after
+    /// locking, which would undo it.
+    pub fn with_agent(self) -> Self {
+        Self {
+            try_agent: true,
+            ..self
+        }
     }
 
-    pub fn data_dir(&self) -> &String {
-        &self.config.data_dir
+    /// Show a message above the password field, e.g., why the file was
+    /// locked.
+    pub fn with_message(self, message: String) -> Self {
+        Self {
+            error_message: Some(message),
+            ..self
+        }
+    }
+
+    pub fn filename(&self) -> &String {
+        &self.filename
     }
 
     #[allow(unused)]
@@ -117,9 +137,11 @@ impl MyApp {
             if new_file_state.new_password.len() > 0
                 && new_file_state.new_password == new_file_state.confirm_password
             {
+                let key = key_derive(&new_file_state.new_password);
+                cache_key(&new_file_state.filename, &key);
                 return Some(Content::PlainText(EditorState::empty(
                     new_file_state.filename.clone(),
-                    new_file_state.new_password.clone(),
+                    key,
                     new_file_state.config().clone(),
                 )));
             }
@@ -149,33 +171,68 @@ impl MyApp {
             }
             ui.allocate_space(Vec2::new(0.0, 10.0));
         }
+        if encrypted_file_state.try_agent {
+            encrypted_file_state.try_agent = false;
+            let filename = encrypted_file_state.filename.clone();
+            let ciphertext = encrypted_file_state.ciphertext.clone();
+            encrypted_file_state.decrypting = Some(Task::spawn(ctx, move || {
+                let key = cached_key(&filename)
+                    .ok_or_else(|| Error::Agent("No key for this file".to_string()))?;
+                let plaintext = PlainText::decrypt_with_key(&key, &ciphertext)
+                    .map_err(|err| Error::Agent(format!("Stale key: {:?}", err)))?;
+                Ok((plaintext, key))
+            }));
+        }
+        let busy = encrypted_file_state.decrypting.is_some()
+            || encrypted_file_state.changing_password.is_some();
         ui.add(
             TextEdit::singleline(&mut encrypted_file_state.password)
                 .password(true)
+                .interactive(!busy)
                 .hint_text("Password"),
         );
         ui.allocate_space(Vec2::new(0.0, 10.0));
-        if ui
+        if (ui
             .button(egui::WidgetText::RichText(
                 RichText::from("Decrypt").size(18.0),
             ))
             .clicked()
-            || (ctx.input(|i| i.key_pressed(egui::Key::Enter)))
+            || (ctx.input(|i| i.key_pressed(egui::Key::Enter))))
+            && !busy
         {
-            match PlainText::decrypt(
-                &encrypted_file_state.password,
-                &encrypted_file_state.ciphertext,
-            ) {
-                Ok(plaintext) => {
+            let password = encrypted_file_state.password.clone();
+            let ciphertext = encrypted_file_state.ciphertext.clone();
+            let filename = encrypted_file_state.filename.clone();
+            encrypted_file_state.decrypting = Some(Task::spawn(ctx, move || {
+                let key = key_derive(&password);
+                let plaintext = PlainText::decrypt_with_key(&key, &ciphertext)?;
+                cache_key(&filename, &key);
+                Ok((plaintext, key))
+            }));
+        }
+        if encrypted_file_state.decrypting.is_some() {
+            ui.add(egui::Spinner::new().size(18.0));
+        }
+        if let Some(result) = encrypted_file_state
+            .decrypting
+            .as_ref()
+            .and_then(|task| task.poll_result())
+        {
+            encrypted_file_state.decrypting = None;
+            match result {
+                Ok((plaintext, key)) => {
                     let editor_state = EditorState::new(
                         encrypted_file_state.filename.clone(),
                         plaintext,
-                        encrypted_file_state.password.clone(),
+                        key,
                         encrypted_file_state.config().clone(),
                         ctx,
-                    );
+                    )
+                    .with_saved_ciphertext(encrypted_file_state.ciphertext.clone());
                     return Some(Content::PlainText(editor_state));
                 }
+                // The agent has no key for this file, or a stale one
+                Err(Error::Agent(_)) => {}
                 Err(_err) => {
                     let mut encrypted_file_state = encrypted_file_state.clone();
                     encrypted_file_state.password = "".to_string();
@@ -233,28 +290,47 @@ impl MyApp {
                 )
                 .clicked()
             {
-                if encrypted_file_state.new_password == encrypted_file_state.confirm_password {
-                    match PlainText::decrypt(
-                        &encrypted_file_state.password,
-                        &encrypted_file_state.ciphertext,
-                    ) {
-                        Ok(plaintext) => {
-                            let ciphertext = plaintext.encrypt(&encrypted_file_state.new_password);
-                            let path = PathBuf::from(encrypted_file_state.data_dir().clone())
-                                .join(format!("{}.safe", encrypted_file_state.filename));
-                            std::fs::write(path, &ciphertext).unwrap();
-                            return Some(Content::Success(
-                                "Password changed successfully".to_string(),
-                            ));
-                        }
-                        Err(_err) => {
-                            let mut encrypted_file_state = encrypted_file_state.clone();
-                            encrypted_file_state.password = "".to_string();
-                            encrypted_file_state.confirm_password = "".to_string();
-                            encrypted_file_state.new_password = "".to_string();
-                            encrypted_file_state.error_message = Some("Wrong password".to_string());
-                            return Some(Content::Encrypted(encrypted_file_state));
-                        }
+                if encrypted_file_state.new_password == encrypted_file_state.confirm_password
+                    && !busy
+                {
+                    let password = encrypted_file_state.password.clone();
+                    let new_password = encrypted_file_state.new_password.clone();
+                    let ciphertext = encrypted_file_state.ciphertext.clone();
+                    let filename = encrypted_file_state.filename.clone();
+                    let storage = encrypted_file_state.config().storage();
+                    encrypted_file_state.changing_password = Some(Task::spawn(ctx, move || {
+                        let plaintext = PlainText::decrypt(&password, &ciphertext)?;
+                        let ciphertext = plaintext.encrypt(&new_password);
+                        storage.write(&filename, ciphertext.as_bytes())
+                    }));
+                }
+            }
+            if encrypted_file_state.changing_password.is_some() {
+                ui.add(egui::Spinner::new().size(18.0));
+            }
+            if let Some(result) = encrypted_file_state
+                .changing_password
+                .as_ref()
+                .and_then(|task| task.poll_result())
+            {
+                encrypted_file_state.changing_password = None;
+                match result {
+                    Ok(()) => {
+                        return Some(Content::Success(
+                            "Password changed successfully".to_string(),
+                        ));
+                    }
+                    Err(err) => {
+                        let mut encrypted_file_state = encrypted_file_state.clone();
+                        encrypted_file_state.password = "".to_string();
+                        encrypted_file_state.confirm_password = "".to_string();
+                        encrypted_file_state.new_password = "".to_string();
+                        encrypted_file_state.error_message = Some(match err {
+                            Error::FailedToWriteFile(err) => format!("Failed to save: {}", err),
+                            Error::TaskPanicked(err) => format!("Failed: {}", err),
+                            _ => "Wrong password".to_string(),
+                        });
+                        return Some(Content::Encrypted(encrypted_file_state));
                     }
                 }
             }
@@ -262,3 +338,17 @@ impl MyApp {
         return None;
     }
 }
+
+/// The key of a file held by the agent, if it is running.
+fn cached_key(file: &str) -> Option<Key> {
+    #[cfg(unix)]
+    return crate::agent::get_key(file);
+    #[cfg(not(unix))]
+    return None;
+}
+
+/// Hand the key of a file to the agent, if it is running.
+fn cache_key(file: &str, key: &Key) {
+    #[cfg(unix)]
+    crate::agent::put_key(file, key);
+}
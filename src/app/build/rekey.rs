@@ -0,0 +1,153 @@
+use crate::{
+    app::{task::Task, MyApp},
+    config::Config,
+    rekey::{rekey_files, RekeyOutcome, RekeyReport},
+};
+
+use eframe::egui;
+use egui::{Color32, RichText, TextEdit, Vec2};
+
+/// Re-encrypt every file in the data dir at once, e.g., after changing a
+/// shared password. Nothing is written until a dry run has gone through
+/// with the same passwords.
+#[derive(Debug, Default, Clone)]
+pub struct RekeyState {
+    password: String,
+    new_password: String,
+    confirm_password: String,
+    config: Config,
+    running: Option<Task<Vec<RekeyReport>>>,
+    /// Files, outcomes and whether they failed, of the last run
+    report: Vec<(String, String, bool)>,
+    /// The passwords the last dry run was done with
+    dry_run_with: Option<(String, String)>,
+    applied: bool,
+}
+
+impl RekeyState {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    fn passwords(&self) -> (String, String) {
+        (self.password.clone(), self.new_password.clone())
+    }
+
+    fn run(&mut self, ctx: &egui::Context, dry_run: bool) {
+        let storage = self.config.storage();
+        let password = self.password.clone();
+        let new_password = self.new_password.clone();
+        // Only the files that passed the dry run are written
+        let files: Option<Vec<String>> = (!dry_run).then(|| {
+            self.report
+                .iter()
+                .filter(|(_, _, failed)| !failed)
+                .map(|(file, _, _)| file.clone())
+                .collect()
+        });
+        self.running = Some(Task::spawn(ctx, move || {
+            let files = match files {
+                Some(files) => files,
+                None => match storage.list() {
+                    Ok(files) => files,
+                    Err(err) => {
+                        return vec![RekeyReport {
+                            file: "".to_string(),
+                            outcome: RekeyOutcome::Failed(err),
+                        }]
+                    }
+                },
+            };
+            let new_password = (!new_password.is_empty()).then_some(new_password.as_str());
+            rekey_files(storage.as_ref(), &files, &password, new_password, dry_run)
+        }));
+    }
+}
+
+impl MyApp {
+    pub(super) fn build_rekey(
+        rekey_state: &mut RekeyState,
+        ctx: &egui::Context,
+        ui: &mut egui::Ui,
+    ) {
+        let busy = rekey_state.running.is_some();
+        ui.label(
+            RichText::from("Re-encrypt all files in the latest format. Leave the new password empty to keep the current one.")
+                .size(14.0),
+        );
+        ui.allocate_space(Vec2::new(0.0, 10.0));
+        for (text, hint) in [
+            (&mut rekey_state.password, "Current Password"),
+            (&mut rekey_state.new_password, "New Password"),
+            (&mut rekey_state.confirm_password, "Confirm Password"),
+        ] {
+            ui.add(
+                TextEdit::singleline(text)
+                    .password(true)
+                    .interactive(!busy && !rekey_state.applied)
+                    .hint_text(hint),
+            );
+        }
+        if rekey_state.dry_run_with.as_ref() != Some(&rekey_state.passwords()) {
+            rekey_state.dry_run_with = None;
+        }
+        let passwords_match = rekey_state.new_password == rekey_state.confirm_password;
+        ui.allocate_space(Vec2::new(0.0, 10.0));
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
+            if ui.button(RichText::from("Dry Run").size(18.0)).clicked()
+                && !busy
+                && !rekey_state.applied
+                && passwords_match
+                && !rekey_state.password.is_empty()
+            {
+                rekey_state.dry_run_with = Some(rekey_state.passwords());
+                rekey_state.run(ctx, true);
+            }
+            let can_apply = rekey_state.dry_run_with.is_some() && !busy && !rekey_state.applied;
+            if ui
+                .button(RichText::from("Apply").size(18.0).color(if can_apply {
+                    Color32::BLACK
+                } else {
+                    Color32::WHITE.gamma_multiply(0.3)
+                }))
+                .clicked()
+                && can_apply
+            {
+                rekey_state.run(ctx, false);
+                rekey_state.applied = true;
+            }
+        });
+        if busy {
+            ui.add(egui::Spinner::new().size(18.0));
+        }
+        if let Some(report) = rekey_state.running.as_ref().and_then(|task| task.poll()) {
+            rekey_state.running = None;
+            rekey_state.report = match report {
+                Ok(report) => report
+                    .into_iter()
+                    .map(|r| (r.file, r.outcome.to_string(), r.outcome.is_failed()))
+                    .collect(),
+                Err(err) => vec![("".to_string(), format!("failed: {:?}", err), true)],
+            };
+        }
+        ui.allocate_space(Vec2::new(0.0, 10.0));
+        egui::ScrollArea::vertical()
+            .id_salt("rekey_report")
+            .show(ui, |ui| {
+                for (file, outcome, failed) in rekey_state.report.iter() {
+                    ui.label(
+                        RichText::from(format!("{}: {}", file, outcome))
+                            .size(14.0)
+                            .color(if *failed {
+                                Color32::RED
+                            } else {
+                                Color32::WHITE
+                            }),
+                    );
+                }
+            });
+    }
+}
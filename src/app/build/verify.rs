@@ -0,0 +1,125 @@
+use crate::{
+    app::{task::Task, MyApp},
+    config::Config,
+    error::Error,
+    verify::{verify_file, VerifyReport},
+};
+
+use eframe::egui;
+use egui::{Color32, RichText, TextEdit, Vec2};
+
+/// Check every file in the data dir, see `verify::verify_file`.
+#[derive(Debug, Default, Clone)]
+pub struct VerifyState {
+    password: String,
+    config: Config,
+    running: Option<Task<Result<Vec<VerifyReport>, Error>>>,
+    /// Files, findings and whether the finding is a problem
+    report: Vec<(String, String, bool)>,
+}
+
+impl VerifyState {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    fn run(&mut self, ctx: &egui::Context) {
+        let storage = self.config.storage();
+        let password = self.password.clone();
+        self.running = Some(Task::spawn(ctx, move || {
+            let password = (!password.is_empty()).then_some(password.as_str());
+            // Failing to list is a failure, not an empty vault
+            Ok(storage
+                .list()?
+                .iter()
+                .map(|file| verify_file(storage.as_ref(), file, password))
+                .collect())
+        }));
+    }
+}
+
+impl MyApp {
+    pub(super) fn build_verify(
+        verify_state: &mut VerifyState,
+        ctx: &egui::Context,
+        ui: &mut egui::Ui,
+    ) {
+        let busy = verify_state.running.is_some();
+        ui.label(
+            RichText::from(
+                "Check all files. Without a password only the layout of the files is checked.",
+            )
+            .size(14.0),
+        );
+        ui.allocate_space(Vec2::new(0.0, 10.0));
+        ui.add(
+            TextEdit::singleline(&mut verify_state.password)
+                .password(true)
+                .interactive(!busy)
+                .hint_text("Password"),
+        );
+        ui.allocate_space(Vec2::new(0.0, 10.0));
+        if (ui.button(RichText::from("Verify").size(18.0)).clicked()
+            || ctx.input(|i| i.key_pressed(egui::Key::Enter)))
+            && !busy
+        {
+            verify_state.run(ctx);
+        }
+        if busy {
+            ui.add(egui::Spinner::new().size(18.0));
+        }
+        if let Some(reports) = verify_state
+            .running
+            .as_ref()
+            .and_then(|task| task.poll_result())
+        {
+            verify_state.running = None;
+            verify_state.report = match reports {
+                Ok(reports) => reports
+                    .into_iter()
+                    .flat_map(|report| {
+                        if report.findings.is_empty() {
+                            let checked = if report.decrypted {
+                                "ok"
+                            } else {
+                                "ok, not decrypted"
+                            };
+                            return vec![(report.file, checked.to_string(), false)];
+                        }
+                        report
+                            .findings
+                            .iter()
+                            .map(|finding| {
+                                (
+                                    report.file.clone(),
+                                    finding.to_string(),
+                                    finding.is_problem(),
+                                )
+                            })
+                            .collect()
+                    })
+                    .collect(),
+                Err(err) => vec![("".to_string(), format!("failed: {:?}", err), true)],
+            };
+        }
+        ui.allocate_space(Vec2::new(0.0, 10.0));
+        egui::ScrollArea::vertical()
+            .id_salt("verify_report")
+            .show(ui, |ui| {
+                for (file, finding, problem) in verify_state.report.iter() {
+                    ui.label(
+                        RichText::from(format!("{}: {}", file, finding))
+                            .size(14.0)
+                            .color(if *problem {
+                                Color32::RED
+                            } else {
+                                Color32::WHITE
+                            }),
+                    );
+                }
+            });
+    }
+}
@@ -1,8 +1,11 @@
 use crate::data_structures::PlainText;
 
 use super::build::{
+    backup::BackupState,
     editor::EditorState,
     locked::{EncryptedFileState, NewFileState},
+    rekey::RekeyState,
+    verify::VerifyState,
 };
 
 #[derive(Default, Clone)]
@@ -12,6 +15,9 @@ pub(crate) enum Content {
     NewFile(NewFileState),
     Encrypted(EncryptedFileState),
     PlainText(EditorState),
+    Rekey(RekeyState),
+    Verify(VerifyState),
+    Backup(BackupState),
     Error(String),
     Success(String),
 }
@@ -32,6 +38,9 @@ impl Content {
             Content::Error(_) => None,
             Content::Success(_) => None,
             Content::NewFile(new_file_state) => Some(new_file_state.filename()),
+            Content::Rekey(_) => None,
+            Content::Verify(_) => None,
+            Content::Backup(_) => None,
             Content::None => None,
         }
     }
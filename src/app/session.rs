@@ -0,0 +1,134 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use eframe::egui;
+
+use crate::config::AutoLockConfig;
+
+/// If the wall clock runs ahead of the monotonic clock by more than this
+/// between two frames, the machine has been suspended in between. The margin
+/// keeps small wall clock adjustments (e.g., by NTP) from locking the file.
+const SUSPEND_THRESHOLD: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockReason {
+    Idle,
+    FocusLost,
+    Suspended,
+}
+
+impl LockReason {
+    pub fn message(&self) -> &'static str {
+        match self {
+            LockReason::Idle => "Locked after being idle",
+            LockReason::FocusLost => "Locked when the window lost focus",
+            LockReason::Suspended => "Locked when the system was suspended",
+        }
+    }
+}
+
+/// What the session is told on every frame.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    pub now: Instant,
+    pub wall_now: SystemTime,
+    /// Whether there was any input since the last frame
+    pub active: bool,
+    /// Whether the window has the focus, if known
+    pub focused: Option<bool>,
+}
+
+/// Tracks the activity while a file is unlocked, to tell when it should be
+/// locked automatically.
+#[derive(Default)]
+pub struct Session {
+    last_activity: Option<Instant>,
+    last_frame: Option<(Instant, SystemTime)>,
+    focused: Option<bool>,
+}
+
+impl Session {
+    /// Forget everything, to be called whenever no file is unlocked, so that
+    /// the next unlocked file starts a fresh session.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Called on every frame while a file is unlocked.
+    pub(crate) fn poll(
+        &mut self,
+        ctx: &egui::Context,
+        config: &AutoLockConfig,
+    ) -> Option<LockReason> {
+        let (active, focused) = ctx.input(|i| {
+            (
+                !i.events.is_empty() || i.pointer.is_moving() || i.pointer.any_down(),
+                i.viewport().focused,
+            )
+        });
+        let frame = Frame {
+            now: Instant::now(),
+            wall_now: SystemTime::now(),
+            active,
+            focused,
+        };
+        let reason = self.check(&frame, config);
+        if reason.is_none() {
+            // Nothing repaints the window while the user is away, so make sure
+            // we get polled again when the time is up.
+            if let Some(after) = self.next_check(&frame, config) {
+                ctx.request_repaint_after(after);
+            }
+        }
+        reason
+    }
+
+    /// Whether the file should be locked as of this frame.
+    pub fn check(&mut self, frame: &Frame, config: &AutoLockConfig) -> Option<LockReason> {
+        let now = frame.now;
+        let last_activity = *self.last_activity.get_or_insert(now);
+        if frame.active {
+            self.last_activity = Some(now);
+        }
+
+        let suspended = self
+            .last_frame
+            .replace((now, frame.wall_now))
+            .map(|(last, wall_last)| {
+                let elapsed = now.duration_since(last);
+                let wall_elapsed = frame.wall_now.duration_since(wall_last).unwrap_or_default();
+                wall_elapsed > elapsed + SUSPEND_THRESHOLD
+            })
+            .unwrap_or(false);
+        if config.on_suspend && suspended {
+            return Some(LockReason::Suspended);
+        }
+
+        let focus_lost = self.focused == Some(true) && frame.focused == Some(false);
+        if frame.focused.is_some() {
+            self.focused = frame.focused;
+        }
+        if config.on_focus_loss && focus_lost {
+            return Some(LockReason::FocusLost);
+        }
+
+        if config.idle_seconds > 0
+            && !frame.active
+            && now.duration_since(last_activity) >= Duration::from_secs(config.idle_seconds)
+        {
+            return Some(LockReason::Idle);
+        }
+        None
+    }
+
+    /// How soon `check` must run again even without input, if at all.
+    pub fn next_check(&self, frame: &Frame, config: &AutoLockConfig) -> Option<Duration> {
+        let idle = (config.idle_seconds > 0).then(|| {
+            let idle = frame
+                .now
+                .duration_since(self.last_activity.unwrap_or(frame.now));
+            Duration::from_secs(config.idle_seconds).saturating_sub(idle)
+        });
+        let suspend = config.on_suspend.then_some(SUSPEND_THRESHOLD);
+        idle.into_iter().chain(suspend).min()
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex},
+};
+
+use eframe::egui;
+
+use crate::error::Error;
+
+/// The result once the work is done, and the signal that it is.
+type Slot<T> = (Mutex<Option<Result<T, Error>>>, Condvar);
+
+/// Work running on a background thread, so that key derivation, encryption
+/// and storage access do not freeze the window. The UI thread polls it on
+/// every frame; the thread asks for a repaint when it is done.
+///
+/// A panic on the thread ends the work with `Error::TaskPanicked` rather than
+/// leaving it running forever.
+#[derive(Debug)]
+pub struct Task<T> {
+    state: Arc<Slot<T>>,
+}
+
+impl<T> Clone for Task<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> Task<T> {
+    pub fn spawn<F: FnOnce() -> T + Send + 'static>(ctx: &egui::Context, f: F) -> Self {
+        let state = Arc::new((Mutex::new(None), Condvar::new()));
+        let thread_state = state.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let result = catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Error::TaskPanicked(message)
+            });
+            *thread_state.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(result);
+            thread_state.1.notify_all();
+            ctx.request_repaint();
+        });
+        Self { state }
+    }
+
+    /// Take the result if the work is done.
+    pub fn poll(&self) -> Option<Result<T, Error>> {
+        self.state.0.lock().unwrap().take()
+    }
+
+    /// Block until the work is done and take the result.
+    pub fn wait(&self) -> Result<T, Error> {
+        let (result, done) = &*self.state;
+        let mut result = done
+            .wait_while(result.lock().unwrap(), |r| r.is_none())
+            .unwrap();
+        result.take().unwrap()
+    }
+}
+
+impl<T: Send + 'static> Task<Result<T, Error>> {
+    /// `poll` for work that can fail, where a panic is one more way to fail.
+    pub fn poll_result(&self) -> Option<Result<T, Error>> {
+        self.poll().map(|result| result.and_then(|r| r))
+    }
+
+    /// `wait` for work that can fail, where a panic is one more way to fail.
+    pub fn wait_result(&self) -> Result<T, Error> {
+        self.wait().and_then(|r| r)
+    }
+}
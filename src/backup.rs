@@ -0,0 +1,413 @@
+use std::{
+    cell::RefCell,
+    fmt::Display,
+    io::{Cursor, Read, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    cipher::{decrypt_to_bytes, encrypt_bytes_with_key, key_derive},
+    error::Error,
+    storage::{check_file_name, Storage},
+};
+
+/// The extension of backup archives.
+pub const BACKUP_EXTENSION: &str = "safebackup";
+
+const MANIFEST: &str = "manifest.json";
+const CONFIG: &str = "config.toml";
+const FILES_DIR: &str = "files/";
+
+/// What a backup holds, with a checksum of every entry to tell a damaged
+/// archive from a good one before anything is restored.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Manifest {
+    /// When the backup was made, RFC 3339
+    pub created: String,
+    pub entries: Vec<ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestEntry {
+    /// Path in the archive
+    pub path: String,
+    pub size: usize,
+    /// SHA-256, in hex
+    pub sha256: String,
+}
+
+/// Every .safe file in a storage, as they are, still encrypted each with
+/// its own password, and the config.
+pub struct Backup {
+    pub manifest: Manifest,
+    /// Names and contents of the .safe files
+    pub files: Vec<(String, Vec<u8>)>,
+    /// The content of config.toml
+    pub config: Option<Vec<u8>>,
+}
+
+impl Backup {
+    /// Take every file in `storage`, and `config` as config.toml if given.
+    pub fn create(storage: &dyn Storage, config: Option<Vec<u8>>) -> Result<Self, Error> {
+        let mut files = vec![];
+        for name in storage.list()? {
+            let content = storage.read(&name)?;
+            files.push((name, content));
+        }
+        let mut entries = files
+            .iter()
+            .map(|(name, content)| manifest_entry(&file_path(name), content))
+            .collect::<Vec<_>>();
+        if let Some(config) = &config {
+            entries.push(manifest_entry(CONFIG, config));
+        }
+        Ok(Self {
+            manifest: Manifest {
+                created: chrono::Utc::now().to_rfc3339(),
+                entries,
+            },
+            files,
+            config,
+        })
+    }
+
+    /// Zip the files with the manifest and encrypt the archive with
+    /// `password`, in the layout of a .safe file.
+    pub fn encrypt(&self, password: &str) -> Result<String, Error> {
+        let map_err = |err: zip::result::ZipError| Error::FailedToWriteFile(format!("{:?}", err));
+        let map_io_err = |err: std::io::Error| Error::FailedToWriteFile(format!("{:?}", err));
+        let manifest = serde_json::to_vec_pretty(&self.manifest)
+            .map_err(|err| Error::FailedToParseJson(format!("{:?}", err)))?;
+        let files = self
+            .files
+            .iter()
+            .map(|(name, content)| (file_path(name), content.as_slice()))
+            .chain(
+                self.config
+                    .iter()
+                    .map(|c| (CONFIG.to_string(), c.as_slice())),
+            )
+            .chain([(MANIFEST.to_string(), manifest.as_slice())]);
+
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        // The .safe files are base64, which deflates well
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (path, content) in files {
+            zip.start_file(path, options).map_err(map_err)?;
+            zip.write_all(content).map_err(map_io_err)?;
+        }
+        let archive = zip.finish().map_err(map_err)?.into_inner();
+        Ok(encrypt_bytes_with_key(&key_derive(password), &archive))
+    }
+
+    /// Decrypt an archive and check every entry of the manifest against
+    /// what is in it. The names of the files are taken as they are;
+    /// `restore_backup` refuses the ones that could point elsewhere.
+    pub fn decrypt(password: &str, ciphertext: &str) -> Result<Self, Error> {
+        let lines = ciphertext.split('\n').collect::<Vec<_>>();
+        if lines.len() < 3 {
+            return Err(Error::InvalidBackup("not a backup archive".to_string()));
+        }
+        let archive = decrypt_to_bytes(&key_derive(password), lines[0], lines[1], lines[2])?;
+        let map_err = |err: zip::result::ZipError| Error::InvalidBackup(format!("{:?}", err));
+        let mut zip = ZipArchive::new(Cursor::new(archive)).map_err(map_err)?;
+        let mut read = |path: &str| -> Result<Vec<u8>, Error> {
+            let mut entry = zip.by_name(path).map_err(map_err)?;
+            let mut content = vec![];
+            entry
+                .read_to_end(&mut content)
+                .map_err(|err| Error::InvalidBackup(format!("{}: {:?}", path, err)))?;
+            Ok(content)
+        };
+
+        let manifest: Manifest = serde_json::from_slice(&read(MANIFEST)?)
+            .map_err(|err| Error::InvalidBackup(format!("{}: {:?}", MANIFEST, err)))?;
+        let mut files = vec![];
+        let mut config = None;
+        for entry in &manifest.entries {
+            let content = read(&entry.path)?;
+            if content.len() != entry.size || sha256(&content) != entry.sha256 {
+                return Err(Error::InvalidBackup(format!(
+                    "{} does not match its checksum",
+                    entry.path
+                )));
+            }
+            if entry.path == CONFIG {
+                config = Some(content);
+            } else if let Some(name) = entry
+                .path
+                .strip_prefix(FILES_DIR)
+                .and_then(|name| name.strip_suffix(".safe"))
+            {
+                files.push((name.to_string(), content));
+            }
+        }
+        Ok(Self {
+            manifest,
+            files,
+            config,
+        })
+    }
+
+    pub fn save(&self, password: &str, path: &Path) -> Result<(), Error> {
+        std::fs::write(path, self.encrypt(password)?).map_err(|err| {
+            Error::FailedToWriteFile(format!("Failed to write {}: {:?}", path.display(), err))
+        })
+    }
+
+    pub fn load(password: &str, path: &Path) -> Result<Self, Error> {
+        let ciphertext = std::fs::read_to_string(path).map_err(|err| {
+            Error::FailedToOpenFile(format!("Failed to read {}: {:?}", path.display(), err))
+        })?;
+        Self::decrypt(password, &ciphertext)
+    }
+}
+
+fn file_path(name: &str) -> String {
+    format!("{}{}.safe", FILES_DIR, name)
+}
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+fn manifest_entry(path: &str, content: &[u8]) -> ManifestEntry {
+    ManifestEntry {
+        path: path.to_string(),
+        size: content.len(),
+        sha256: sha256(content),
+    }
+}
+
+/// What to do with a file in the backup whose name is taken by a different
+/// file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnConflict {
+    #[default]
+    Skip,
+    Overwrite,
+    /// Restore under a new name, such as "name (2)"
+    Rename,
+}
+
+#[derive(Debug)]
+pub enum RestoreOutcome {
+    Restored,
+    /// A file with the same name and content is there already
+    Unchanged,
+    Skipped,
+    Overwritten,
+    Renamed(String),
+    /// Written under this name, which is not read, as config.toml is kept
+    SetAside(String),
+    Failed(Error),
+}
+
+impl RestoreOutcome {
+    pub fn is_failed(&self) -> bool {
+        matches!(self, RestoreOutcome::Failed(_))
+    }
+}
+
+impl Display for RestoreOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RestoreOutcome::Restored => write!(f, "restored"),
+            RestoreOutcome::Unchanged => write!(f, "already there"),
+            RestoreOutcome::Skipped => write!(f, "skipped, the name is taken"),
+            RestoreOutcome::Overwritten => write!(f, "overwritten"),
+            RestoreOutcome::Renamed(name) => write!(f, "restored as {}", name),
+            RestoreOutcome::SetAside(name) => {
+                write!(
+                    f,
+                    "saved as {}, which is not used; the current one is kept",
+                    name
+                )
+            }
+            RestoreOutcome::Failed(err) => write!(f, "failed: {:?}", err),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RestoreReport {
+    pub file: String,
+    pub outcome: RestoreOutcome,
+}
+
+/// Write the files of a backup into `storage`, and its config to
+/// `config_file` if given, keeping the data dir and the storage of the one
+/// there. Names that are taken by different content are handled as
+/// `on_conflict` says.
+pub fn restore_backup(
+    storage: &dyn Storage,
+    backup: &Backup,
+    config_file: Option<&Path>,
+    on_conflict: OnConflict,
+) -> Vec<RestoreReport> {
+    let mut reports = vec![];
+    // Names written by this restore are taken too
+    let existing = match storage.list() {
+        Ok(existing) => RefCell::new(existing),
+        Err(err) => {
+            return vec![RestoreReport {
+                file: "".to_string(),
+                outcome: RestoreOutcome::Failed(err),
+            }]
+        }
+    };
+    for (name, content) in &backup.files {
+        // The archive may have been made by hand
+        if let Err(err) = check_file_name(name) {
+            reports.push(RestoreReport {
+                file: name.clone(),
+                outcome: RestoreOutcome::Failed(err),
+            });
+            continue;
+        }
+        let outcome = restore(
+            name,
+            content,
+            on_conflict,
+            |name| existing.borrow().iter().any(|n| n == name),
+            |name| storage.read(name),
+            |name, content| {
+                storage.write(name, content)?;
+                existing.borrow_mut().push(name.to_string());
+                Ok(())
+            },
+        );
+        reports.push(RestoreReport {
+            file: name.clone(),
+            outcome,
+        });
+    }
+
+    if let (Some(config), Some(config_file)) = (&backup.config, config_file) {
+        reports.push(RestoreReport {
+            file: CONFIG.to_string(),
+            outcome: restore_config(config, config_file, on_conflict),
+        });
+    }
+    reports
+}
+
+/// Settings that say where the files are. They are kept from the local
+/// config, or the files just restored would no longer be the ones used.
+const LOCAL_KEYS: [&str; 2] = ["data_dir", "storage"];
+
+/// Restore the settings of a backup, keeping where the files are. Only
+/// `config_file` is read, so a config restored under another name is set
+/// aside for the user to look at.
+fn restore_config(config: &[u8], config_file: &Path, on_conflict: OnConflict) -> RestoreOutcome {
+    let parse = |content: &[u8], name: &str| -> Result<toml::Table, Error> {
+        std::str::from_utf8(content)
+            .map_err(|_| Error::InvalidUTF8)
+            .and_then(|content| {
+                toml::from_str(content)
+                    .map_err(|err| Error::InvalidConfig(format!("{}: {}", name, err)))
+            })
+    };
+    // None if the settings are the same as the local ones
+    let merged = (|| -> Result<Option<Vec<u8>>, Error> {
+        let mut config = parse(config, "the backup's config.toml")?;
+        if config_file.exists() {
+            let local = std::fs::read(config_file).map_err(|err| {
+                Error::FailedToOpenFile(format!("Failed to read {}: {:?}", CONFIG, err))
+            })?;
+            let local = parse(&local, CONFIG)?;
+            for key in LOCAL_KEYS {
+                match local.get(key) {
+                    Some(value) => config.insert(key.to_string(), value.clone()),
+                    None => config.remove(key),
+                };
+            }
+            if config == local {
+                return Ok(None);
+            }
+        }
+        let config =
+            toml::to_string(&config).map_err(|err| Error::InvalidConfig(format!("{}", err)))?;
+        Ok(Some(config.into_bytes()))
+    })();
+    let config = match merged {
+        Ok(Some(config)) => config,
+        Ok(None) => return RestoreOutcome::Unchanged,
+        Err(err) => return RestoreOutcome::Failed(err),
+    };
+
+    let path_of = |name: &str| config_file.with_file_name(format!("{}.toml", name));
+    let stem = config_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let outcome = restore(
+        &stem,
+        &config,
+        on_conflict,
+        |name| path_of(name).exists(),
+        |name| {
+            std::fs::read(path_of(name)).map_err(|err| {
+                Error::FailedToOpenFile(format!("Failed to read {}: {:?}", name, err))
+            })
+        },
+        |name, content| {
+            std::fs::write(path_of(name), content).map_err(|err| {
+                Error::FailedToWriteFile(format!("Failed to write {}: {:?}", name, err))
+            })
+        },
+    );
+    match outcome {
+        RestoreOutcome::Renamed(name) => RestoreOutcome::SetAside(format!("{}.toml", name)),
+        outcome => outcome,
+    }
+}
+
+/// Restore one file, wherever it is kept, by its name.
+fn restore(
+    name: &str,
+    content: &[u8],
+    on_conflict: OnConflict,
+    exists: impl Fn(&str) -> bool,
+    read: impl Fn(&str) -> Result<Vec<u8>, Error>,
+    write: impl Fn(&str, &[u8]) -> Result<(), Error>,
+) -> RestoreOutcome {
+    let result = (|| {
+        if !exists(name) {
+            write(name, content)?;
+            return Ok(RestoreOutcome::Restored);
+        }
+        if read(name)? == content {
+            return Ok(RestoreOutcome::Unchanged);
+        }
+        match on_conflict {
+            OnConflict::Skip => Ok(RestoreOutcome::Skipped),
+            OnConflict::Overwrite => {
+                write(name, content)?;
+                Ok(RestoreOutcome::Overwritten)
+            }
+            OnConflict::Rename => {
+                let renamed = (2..)
+                    .map(|n| format!("{} ({})", name, n))
+                    .find(|renamed| !exists(renamed))
+                    .unwrap();
+                write(&renamed, content)?;
+                Ok(RestoreOutcome::Renamed(renamed))
+            }
+        }
+    })();
+    result.unwrap_or_else(RestoreOutcome::Failed)
+}
+
+/// A name for a new backup, e.g., "safe-writing-2024-01-31.safebackup".
+pub fn default_backup_file_name() -> String {
+    format!(
+        "safe-writing-{}.{}",
+        chrono::Local::now().format("%Y-%m-%d"),
+        BACKUP_EXTENSION
+    )
+}
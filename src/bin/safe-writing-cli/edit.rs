@@ -0,0 +1,137 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use safe_writing_rs::error::Error;
+
+/// A file holding decrypted text. It is overwritten with zeros and removed
+/// when dropped, whether or not the edit succeeded.
+struct SecureTempFile {
+    path: PathBuf,
+}
+
+impl SecureTempFile {
+    fn create(content: &str) -> Result<Self, Error> {
+        let dir = secure_temp_dir().ok_or(Error::NoSecureTempDir)?;
+        let mut suffix = [0u8; 8];
+        StdRng::from_os_rng().fill_bytes(&mut suffix);
+        let path = dir.join(format!(
+            "safe-writing-{}.txt",
+            suffix
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        ));
+        let mut file =
+            create_private(&path).map_err(|err| Error::FailedToWriteFile(format!("{:?}", err)))?;
+        let temp_file = Self { path };
+        file.write_all(content.as_bytes())
+            .map_err(|err| Error::FailedToWriteFile(format!("{:?}", err)))?;
+        Ok(temp_file)
+    }
+
+    fn read(&self) -> Result<String, Error> {
+        let data = std::fs::read(&self.path)
+            .map_err(|err| Error::FailedToOpenFile(format!("{:?}", err)))?;
+        String::from_utf8(data).map_err(|_| Error::InvalidUTF8)
+    }
+
+    fn wipe(&self) -> std::io::Result<()> {
+        let mut file = std::fs::OpenOptions::new().write(true).open(&self.path)?;
+        let len = file.metadata()?.len();
+        file.seek(SeekFrom::Start(0))?;
+        std::io::copy(&mut std::io::repeat(0).take(len), &mut file)?;
+        file.sync_all()
+    }
+}
+
+impl Drop for SecureTempFile {
+    fn drop(&mut self) {
+        if let Err(err) = self.wipe() {
+            eprintln!("Failed to wipe {}: {}", self.path.display(), err);
+        }
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            eprintln!("Failed to remove {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn create_private(path: &Path) -> std::io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private(_path: &Path) -> std::io::Result<File> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// A directory in memory backed storage, so that the decrypted text never
+/// reaches a disk. $XDG_RUNTIME_DIR is only readable by the user, which is
+/// better than the world readable /dev/shm.
+#[cfg(target_os = "linux")]
+fn secure_temp_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(std::iter::once(PathBuf::from("/dev/shm")))
+        .find(|dir| is_in_memory(dir))
+}
+
+#[cfg(target_os = "linux")]
+fn is_in_memory(dir: &Path) -> bool {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    const TMPFS_MAGIC: i64 = 0x01021994;
+    const RAMFS_MAGIC: i64 = 0x858458f6;
+
+    let Ok(path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return false;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    let f_type = stat.f_type as i64;
+    f_type == TMPFS_MAGIC || f_type == RAMFS_MAGIC
+}
+
+#[cfg(not(target_os = "linux"))]
+fn secure_temp_dir() -> Option<PathBuf> {
+    None
+}
+
+/// Open the text in the user's editor and return the text after the editor
+/// exits.
+pub fn edit(content: &str) -> Result<String, Error> {
+    let temp_file = SecureTempFile::create(content)?;
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // Run through the shell so that the editor may come with arguments, e.g.,
+    // "code --wait"
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&temp_file.path)
+        .status()
+        .map_err(|err| Error::EditorFailed(format!("{:?}", err)))?;
+    if !status.success() {
+        return Err(Error::EditorFailed(format!(
+            "{} exited with {}",
+            editor, status
+        )));
+    }
+    temp_file.read()
+}
@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+
+use clap::Args;
+use regex::{Regex, RegexBuilder};
+use safe_writing_rs::{
+    cipher::key_derive,
+    data_structures::{image_reference, PlainText},
+    error::{Error, ErrorKind},
+    storage::Storage,
+};
+use serde::Serialize;
+
+use crate::password;
+
+#[derive(Args)]
+pub struct GrepArgs {
+    pattern: String,
+    /// Files to search, all by default
+    files: Vec<String>,
+    #[arg(short, long)]
+    ignore_case: bool,
+    /// Lines of context around each match
+    #[arg(short = 'C', long, default_value_t = 0)]
+    context: usize,
+    /// Ask for the password of each file, instead of one for all
+    #[arg(long)]
+    prompt_each: bool,
+}
+
+#[derive(Serialize)]
+struct Line {
+    line: usize,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct Match {
+    file: String,
+    /// Position of the passage, starting from 1
+    passage: usize,
+    title: String,
+    /// None if the title matches
+    line: Option<Line>,
+    context_before: Vec<Line>,
+    context_after: Vec<Line>,
+}
+
+/// A file that could not be searched.
+#[derive(Serialize)]
+struct FileError {
+    file: String,
+    kind: ErrorKind,
+    message: String,
+}
+
+/// The lines of a passage with their line numbers starting from 1, leaving
+/// out the image placeholders, which are never matched nor shown.
+fn text_lines(content: &str) -> Vec<(usize, &str)> {
+    content
+        .split('\n')
+        .enumerate()
+        .filter(|(_, line)| image_reference(line).is_none())
+        .map(|(i, line)| (i + 1, line))
+        .collect()
+}
+
+fn search(file: &str, plaintext: &PlainText, regex: &Regex, context: usize) -> Vec<Match> {
+    let to_line = |(line, text): &(usize, &str)| Line {
+        line: *line,
+        text: text.to_string(),
+    };
+    let mut matches = vec![];
+    for (i, passage) in plaintext.passages().iter().enumerate() {
+        if regex.is_match(passage.title()) {
+            matches.push(Match {
+                file: file.to_string(),
+                passage: i + 1,
+                title: passage.title().clone(),
+                line: None,
+                context_before: vec![],
+                context_after: vec![],
+            });
+        }
+        let lines = text_lines(passage.content());
+        for (j, line) in lines.iter().enumerate() {
+            if regex.is_match(line.1) {
+                matches.push(Match {
+                    file: file.to_string(),
+                    passage: i + 1,
+                    title: passage.title().clone(),
+                    line: Some(to_line(line)),
+                    context_before: lines[j.saturating_sub(context)..j]
+                        .iter()
+                        .map(to_line)
+                        .collect(),
+                    context_after: lines[j + 1..(j + 1 + context).min(lines.len())]
+                        .iter()
+                        .map(to_line)
+                        .collect(),
+                });
+            }
+        }
+    }
+    matches
+}
+
+/// Print the matches like grep does: "file / title / 12:line" for a match,
+/// "-" instead of ":" for context, and "--" between groups that are apart.
+fn print_matches(matches: &[Match], context: usize) {
+    let mut last_group: Option<(&str, usize, usize)> = None;
+    for group in matches.chunk_by(|a, b| a.file == b.file && a.passage == b.passage) {
+        let (file, title) = (&group[0].file, &group[0].title);
+        // Line number to the text and whether it matches
+        let mut lines = BTreeMap::new();
+        for m in group {
+            let Some(line) = &m.line else {
+                println!("{} / {}", file, title);
+                continue;
+            };
+            for l in m.context_before.iter().chain(m.context_after.iter()) {
+                lines.entry(l.line).or_insert((&l.text, false));
+            }
+            lines.insert(line.line, (&line.text, true));
+        }
+        for (line, (text, is_match)) in lines {
+            if let Some((last_file, last_passage, last_line)) = last_group {
+                let adjacent =
+                    last_file == file && last_passage == group[0].passage && last_line + 1 == line;
+                if context > 0 && !adjacent {
+                    println!("--");
+                }
+            }
+            let marker = if is_match { ':' } else { '-' };
+            println!("{} / {} / {}{}{}", file, title, line, marker, text);
+            last_group = Some((file, group[0].passage, line));
+        }
+    }
+}
+
+pub fn grep(storage: &dyn Storage, args: GrepArgs, json: bool) -> Result<(), Error> {
+    let regex = RegexBuilder::new(&args.pattern)
+        .case_insensitive(args.ignore_case)
+        .build()
+        .map_err(|err| Error::InvalidPattern(format!("{}", err)))?;
+    let files = if args.files.is_empty() {
+        storage.list()?
+    } else {
+        args.files
+    };
+    // Only asked for when the agent does not have the key of some file
+    let mut shared_password: Option<String> = None;
+
+    let mut matches = vec![];
+    let mut errors = vec![];
+    for file in files {
+        let cached =
+            password::cached_key(&file).and_then(|key| storage.load_with_key(&file, &key).ok());
+        let loaded = match cached {
+            Some(plaintext) => Ok(plaintext),
+            None => {
+                let password = match &shared_password {
+                    Some(password) => password.clone(),
+                    None if args.prompt_each => {
+                        password::prompt(&format!("Password for {}: ", file))?
+                    }
+                    None => shared_password
+                        .insert(password::prompt("Password: ")?)
+                        .clone(),
+                };
+                let key = key_derive(&password);
+                let loaded = storage.load_with_key(&file, &key);
+                if loaded.is_ok() {
+                    password::cache_key(&file, &key);
+                }
+                loaded
+            }
+        };
+        match loaded {
+            Ok(plaintext) => matches.extend(search(&file, &plaintext, &regex, args.context)),
+            Err(err) => {
+                if !json {
+                    eprintln!("Skipping {}: {:?}", file, err);
+                }
+                errors.push((file, err));
+            }
+        }
+    }
+
+    if json {
+        let errors = errors
+            .iter()
+            .map(|(file, err)| FileError {
+                file: file.clone(),
+                kind: err.kind(),
+                message: format!("{:?}", err),
+            })
+            .collect::<Vec<_>>();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "matches": matches,
+                "errors": errors,
+            }))
+            .unwrap()
+        );
+    } else {
+        print_matches(&matches, args.context);
+    }
+    // Exit with a failure if any of the files could not be searched
+    match errors.into_iter().next() {
+        Some((_, err)) => Err(err),
+        None => Ok(()),
+    }
+}
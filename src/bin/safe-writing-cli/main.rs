@@ -0,0 +1,440 @@
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use safe_writing_rs::{
+    cipher::{key_derive, Key},
+    config::Config,
+    data_structures::{PassageRef, PlainText},
+    error::{Error, ErrorKind},
+    rekey::{rekey_files, RekeyOutcome},
+    storage::Storage,
+};
+use serde::Serialize;
+
+mod edit;
+mod grep;
+mod password;
+mod verify;
+
+/// Scripted access to the Safe Writing vault. The files are the ones in the
+/// data dir of the same config.toml the app uses.
+///
+/// Exit codes: 1 for other errors, 2 for invalid arguments, 3 for a wrong
+/// password, 4 for a corrupted file, 5 for a missing file or passage, and 6
+/// for I/O errors.
+#[derive(Parser)]
+#[command(name = "safe-writing-cli")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    #[command(flatten)]
+    password: password::PasswordArgs,
+    /// Print the result as JSON, and errors as JSON on stderr
+    #[arg(long, global = true)]
+    json: bool,
+}
+
+/// Exit codes for scripts, besides 0 for success and 2 for invalid
+/// arguments.
+fn exit_code(kind: ErrorKind) -> u8 {
+    match kind {
+        ErrorKind::Other => 1,
+        ErrorKind::WrongPassword => 3,
+        ErrorKind::Corrupted => 4,
+        ErrorKind::NotFound => 5,
+        ErrorKind::Io => 6,
+    }
+}
+
+#[derive(Serialize)]
+struct PassageInfo {
+    /// Starting from 1, as taken by the commands
+    position: usize,
+    title: String,
+}
+
+#[derive(Serialize)]
+struct FileInfo<'a> {
+    file: &'a str,
+    passages: Vec<PassageInfo>,
+}
+
+impl<'a> FileInfo<'a> {
+    fn new(file: &'a str, plaintext: &PlainText) -> Self {
+        Self {
+            file,
+            passages: plaintext
+                .passages()
+                .iter()
+                .enumerate()
+                .map(|(i, passage)| PassageInfo {
+                    position: i + 1,
+                    title: passage.title().clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RekeyEntry {
+    file: String,
+    outcome: &'static str,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ErrorInfo {
+    kind: ErrorKind,
+    message: String,
+    exit_code: u8,
+}
+
+fn print_json<T: Serialize>(value: &T) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap());
+}
+
+/// A passage is given either by its position as printed by `passages`,
+/// starting from 1, or by its title.
+#[derive(Subcommand)]
+enum Command {
+    /// List the files
+    List,
+    /// List the passages of a file
+    Passages { file: String },
+    /// Print the content of a passage
+    Cat { file: String, passage: String },
+    /// Add a passage with the content read from stdin
+    Add {
+        file: String,
+        title: String,
+        /// Position of the new passage, at the end by default
+        #[arg(long)]
+        at: Option<usize>,
+    },
+    /// Append the text read from stdin to a passage
+    Append { file: String, passage: String },
+    /// Remove a passage
+    Rm { file: String, passage: String },
+    /// Move a passage to another position
+    Mv {
+        file: String,
+        passage: String,
+        to: usize,
+    },
+    /// Change the password of a file
+    Passwd { file: String },
+    /// Edit a passage in $VISUAL or $EDITOR. The decrypted text only lives
+    /// in memory backed storage while the editor runs.
+    Edit { file: String, passage: String },
+    /// Search the titles and contents of the passages with a regex
+    Grep(grep::GrepArgs),
+    /// Re-encrypt files in the latest format, optionally with a new
+    /// password. Only reports what would happen unless --apply is given.
+    Rekey {
+        /// The files to re-encrypt, all files by default
+        files: Vec<String>,
+        /// Ask for a new password for all the files
+        #[arg(long)]
+        new_password: bool,
+        /// Write the files instead of doing a dry run
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Check that the files are well formed and, given the password, that
+    /// they decrypt and every image placeholder has its image
+    Verify(verify::VerifyArgs),
+    /// Keep the keys of opened files, so that each password is asked once
+    #[cfg(unix)]
+    #[command(subcommand)]
+    Agent(AgentCommand),
+}
+
+#[cfg(unix)]
+#[derive(Subcommand)]
+enum AgentCommand {
+    /// Run the agent in the foreground, e.g., with `&` from the shell profile
+    Start {
+        /// Seconds to keep each key, agent.key_timeout_seconds in config.toml
+        /// by default
+        #[arg(long)]
+        timeout: Option<u64>,
+    },
+    /// Forget the keys of all files
+    LockAll,
+    /// Forget the key of a file
+    Forget { file: String },
+}
+
+/// Resolve a passage given on the command line to its index.
+fn passage_index(plaintext: &PlainText, passage: &str) -> Result<usize, Error> {
+    if let Ok(position) = passage.parse::<usize>() {
+        if position >= 1 && position <= plaintext.num_passages() {
+            return Ok(position - 1);
+        }
+    }
+    plaintext
+        .passages()
+        .iter()
+        .position(|p| p.title() == passage)
+        .ok_or_else(|| Error::PassageNotFound(passage.to_string()))
+}
+
+/// A position given on the command line, starting from 1.
+fn position_index(position: usize) -> Result<usize, Error> {
+    position
+        .checked_sub(1)
+        .ok_or(Error::PassageIndexOutOfRange(position))
+}
+
+fn read_stdin() -> Result<String, Error> {
+    std::io::read_to_string(std::io::stdin())
+        .map_err(|err| Error::FailedToOpenFile(format!("Failed to read stdin: {:?}", err)))
+}
+
+/// Decrypt a file with the key from the agent, or ask for the password and
+/// hand the key to the agent. The password of a file never saved is asked
+/// twice, as it becomes the password of the file.
+fn open(storage: &dyn Storage, file: &str) -> Result<(PlainText, Key), Error> {
+    let never_saved = storage.read(file)?.is_empty();
+    if let Some(key) = password::cached_key(file).filter(|_| !never_saved) {
+        // The password may have been changed since
+        if let Ok(plaintext) = storage.load_with_key(file, &key) {
+            return Ok((plaintext, key));
+        }
+    }
+    let password = if never_saved {
+        password::prompt_first(file)?
+    } else {
+        password::prompt(&format!("Password for {}: ", file))?
+    };
+    let key = key_derive(&password);
+    let plaintext = storage.load_with_key(file, &key)?;
+    password::cache_key(file, &key);
+    Ok((plaintext, key))
+}
+
+/// Commands that change a file print its passages afterwards with --json.
+fn run(command: Command, json: bool) -> Result<(), Error> {
+    let config = Config::load()?;
+    let storage = config.storage();
+    let storage = storage.as_ref();
+    match command {
+        Command::List => {
+            let files = storage.list()?;
+            if json {
+                print_json(&serde_json::json!({ "files": files }));
+            } else {
+                for file_name in files {
+                    println!("{}", file_name);
+                }
+            }
+        }
+        Command::Passages { file } => {
+            let (plaintext, _) = open(storage, &file)?;
+            if json {
+                print_json(&FileInfo::new(&file, &plaintext));
+            } else {
+                for (i, passage) in plaintext.passages().iter().enumerate() {
+                    println!("{}\t{}", i + 1, passage.title());
+                }
+            }
+        }
+        Command::Cat { file, passage } => {
+            let (plaintext, _) = open(storage, &file)?;
+            let index = passage_index(&plaintext, &passage)?;
+            let passage = plaintext.passage(index)?;
+            if json {
+                print_json(&serde_json::json!({
+                    "file": file,
+                    "position": index + 1,
+                    "title": passage.title(),
+                    "content": passage.content(),
+                }));
+            } else {
+                println!("{}", passage.content());
+            }
+        }
+        Command::Add { file, title, at } => {
+            let content = read_stdin()?;
+            let (mut plaintext, key) = open(storage, &file)?;
+            let index = match at {
+                Some(at) => position_index(at)?,
+                None => plaintext.num_passages(),
+            };
+            let id = plaintext.insert_new_passage(index, title)?;
+            plaintext.set_content(PassageRef::Id(id), content)?;
+            storage.save_with_key(&file, &plaintext, &key)?;
+            if json {
+                print_json(&FileInfo::new(&file, &plaintext));
+            }
+        }
+        Command::Append { file, passage } => {
+            let text = read_stdin()?;
+            let (mut plaintext, key) = open(storage, &file)?;
+            let index = passage_index(&plaintext, &passage)?;
+            let content = plaintext.passage(index)?.content();
+            // The same as reading the temp file in the app
+            let content = if content.is_empty() {
+                text.trim().to_string()
+            } else {
+                format!("{}\n\n{}", content, text.trim())
+            };
+            plaintext.set_content(index, content)?;
+            storage.save_with_key(&file, &plaintext, &key)?;
+            if json {
+                print_json(&FileInfo::new(&file, &plaintext));
+            }
+        }
+        Command::Rm { file, passage } => {
+            let (mut plaintext, key) = open(storage, &file)?;
+            let index = passage_index(&plaintext, &passage)?;
+            plaintext.remove_passage(index)?;
+            storage.save_with_key(&file, &plaintext, &key)?;
+            if json {
+                print_json(&FileInfo::new(&file, &plaintext));
+            }
+        }
+        Command::Mv { file, passage, to } => {
+            let (mut plaintext, key) = open(storage, &file)?;
+            let index = passage_index(&plaintext, &passage)?;
+            plaintext.move_passage(index, position_index(to)?)?;
+            storage.save_with_key(&file, &plaintext, &key)?;
+            if json {
+                print_json(&FileInfo::new(&file, &plaintext));
+            }
+        }
+        Command::Passwd { file } => {
+            let (plaintext, _) = open(storage, &file)?;
+            let new_password = password::prompt_new(&file)?;
+            let key = key_derive(&new_password);
+            storage.save_with_key(&file, &plaintext, &key)?;
+            password::cache_key(&file, &key);
+            if json {
+                print_json(&FileInfo::new(&file, &plaintext));
+            }
+        }
+        Command::Edit { file, passage } => {
+            let (mut plaintext, key) = open(storage, &file)?;
+            let index = passage_index(&plaintext, &passage)?;
+            let content = plaintext.passage(index)?.content().clone();
+            let edited = edit::edit(&content)?;
+            // The images stay in the file whatever happens to the
+            // placeholders, the same as in the app
+            let changed = edited != content;
+            if changed {
+                plaintext.set_content(index, edited)?;
+                storage.save_with_key(&file, &plaintext, &key)?;
+            }
+            if json {
+                print_json(&serde_json::json!({
+                    "file": file,
+                    "position": index + 1,
+                    "changed": changed,
+                }));
+            }
+        }
+        Command::Grep(args) => grep::grep(storage, args, json)?,
+        Command::Verify(args) => verify::verify(storage, args, json)?,
+        #[cfg(unix)]
+        Command::Agent(command) => match command {
+            AgentCommand::Start { timeout } => {
+                let path = safe_writing_rs::agent::socket_path();
+                let agent = safe_writing_rs::agent::Agent::bind(
+                    &path,
+                    timeout
+                        .map(std::time::Duration::from_secs)
+                        .unwrap_or_else(|| config.agent_key_timeout()),
+                )?;
+                eprintln!("Agent listening on {}", path.display());
+                agent.serve(|err| eprintln!("Agent: {:?}", err));
+            }
+            AgentCommand::LockAll => {
+                safe_writing_rs::agent::lock_all()?;
+                if json {
+                    print_json(&serde_json::json!({}));
+                }
+            }
+            AgentCommand::Forget { file } => {
+                safe_writing_rs::agent::forget(&file)?;
+                if json {
+                    print_json(&serde_json::json!({ "file": file }));
+                }
+            }
+        },
+        Command::Rekey {
+            files,
+            new_password,
+            apply,
+        } => {
+            let files = if files.is_empty() {
+                storage.list()?
+            } else {
+                files
+            };
+            let password = password::prompt("Current password: ")?;
+            let new_password = if new_password {
+                Some(password::prompt_new("all files")?)
+            } else {
+                None
+            };
+            let reports = rekey_files(storage, &files, &password, new_password.as_deref(), !apply);
+            if json {
+                let files = reports
+                    .iter()
+                    .map(|report| RekeyEntry {
+                        file: report.file.clone(),
+                        outcome: match &report.outcome {
+                            RekeyOutcome::WouldRekey => "would_rekey",
+                            RekeyOutcome::Rekeyed => "rekeyed",
+                            RekeyOutcome::SkippedEmpty => "skipped_empty",
+                            RekeyOutcome::Failed(_) => "failed",
+                        },
+                        error: match &report.outcome {
+                            RekeyOutcome::Failed(err) => Some(format!("{:?}", err)),
+                            _ => None,
+                        },
+                    })
+                    .collect::<Vec<_>>();
+                print_json(&serde_json::json!({ "dry_run": !apply, "files": files }));
+            } else {
+                for report in reports.iter() {
+                    println!("{}\t{}", report.file, report.outcome);
+                }
+                if !apply {
+                    println!("Dry run, nothing written. Run again with --apply to re-encrypt.");
+                }
+            }
+            // Exit with a failure if any of the files failed
+            if let Some(RekeyOutcome::Failed(err)) = reports
+                .into_iter()
+                .map(|r| r.outcome)
+                .find(RekeyOutcome::is_failed)
+            {
+                return Err(err);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match password::init(cli.password).and_then(|_| run(cli.command, cli.json)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            let exit_code = exit_code(err.kind());
+            if cli.json {
+                let error = ErrorInfo {
+                    kind: err.kind(),
+                    message: format!("{:?}", err),
+                    exit_code,
+                };
+                eprintln!("{}", serde_json::to_string_pretty(&error).unwrap());
+            } else {
+                eprintln!("Error: {:?}", err);
+            }
+            ExitCode::from(exit_code)
+        }
+    }
+}
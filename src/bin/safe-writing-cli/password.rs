@@ -0,0 +1,169 @@
+use std::sync::{Mutex, OnceLock};
+
+use clap::Args;
+use safe_writing_rs::{cipher::Key, error::Error, password::PasswordSource};
+
+/// Supply the passwords without a terminal. Each password is read once and
+/// used for every file. With stdin, the current password is the first line
+/// and a new password the next one; the rest is left for the command.
+#[derive(Args)]
+pub struct PasswordArgs {
+    /// Read the password from the first line of this file descriptor
+    #[arg(long, global = true, value_name = "FD", value_parser = clap::value_parser!(i32).range(0..))]
+    password_fd: Option<i32>,
+    /// Read the password from this environment variable
+    #[arg(long, global = true, value_name = "NAME")]
+    password_env: Option<String>,
+    /// Use the first line printed by this shell command as the password
+    #[arg(long, global = true, value_name = "COMMAND")]
+    password_command: Option<String>,
+    /// Read the password from the first line of stdin
+    #[arg(long, global = true)]
+    password_stdin: bool,
+    /// Read the new password from the first line of this file descriptor
+    #[arg(long, global = true, value_name = "FD", value_parser = clap::value_parser!(i32).range(0..))]
+    new_password_fd: Option<i32>,
+    /// Read the new password from this environment variable
+    #[arg(long, global = true, value_name = "NAME")]
+    new_password_env: Option<String>,
+    /// Use the first line printed by this shell command as the new password
+    #[arg(long, global = true, value_name = "COMMAND")]
+    new_password_command: Option<String>,
+    /// Read the new password from the next line of stdin
+    #[arg(long, global = true)]
+    new_password_stdin: bool,
+}
+
+fn source(
+    fd: Option<i32>,
+    env: Option<String>,
+    command: Option<String>,
+    stdin: bool,
+) -> Result<Option<PasswordSource>, Error> {
+    let mut sources = fd
+        .map(PasswordSource::Fd)
+        .into_iter()
+        .chain(env.map(PasswordSource::Env))
+        .chain(command.map(PasswordSource::Command))
+        .chain(stdin.then_some(PasswordSource::Stdin));
+    let source = sources.next();
+    if sources.next().is_some() {
+        return Err(Error::FailedToReadPassword(
+            "Only one way to read each password can be given".to_string(),
+        ));
+    }
+    Ok(source)
+}
+
+/// A password source together with what it has read, so that it is only
+/// read once.
+struct Supplied {
+    source: PasswordSource,
+    password: Mutex<Option<String>>,
+}
+
+impl Supplied {
+    fn read(&self) -> Result<String, Error> {
+        let mut password = self.password.lock().unwrap();
+        if password.is_none() {
+            *password = Some(self.source.read()?);
+        }
+        Ok(password.clone().unwrap())
+    }
+}
+
+static CURRENT: OnceLock<Option<Supplied>> = OnceLock::new();
+static NEW: OnceLock<Option<Supplied>> = OnceLock::new();
+
+/// Take the password options from the command line. Without them, the
+/// passwords are asked for on the terminal.
+pub fn init(args: PasswordArgs) -> Result<(), Error> {
+    let supplied = |source: Option<PasswordSource>| {
+        source.map(|source| Supplied {
+            source,
+            password: Mutex::new(None),
+        })
+    };
+    let current = source(
+        args.password_fd,
+        args.password_env,
+        args.password_command,
+        args.password_stdin,
+    )?;
+    let new = source(
+        args.new_password_fd,
+        args.new_password_env,
+        args.new_password_command,
+        args.new_password_stdin,
+    )?;
+    let current = supplied(current);
+    let new = supplied(new);
+    // Take the password lines off stdin before a command reads its input
+    for supplied in current.iter().chain(new.iter()) {
+        if supplied.source == PasswordSource::Stdin {
+            supplied.read()?;
+        }
+    }
+    let _ = CURRENT.set(current);
+    let _ = NEW.set(new);
+    Ok(())
+}
+
+pub fn prompt(prompt: &str) -> Result<String, Error> {
+    if let Some(Some(supplied)) = CURRENT.get() {
+        return supplied.read();
+    }
+    rpassword::prompt_password(prompt)
+        .map_err(|err| Error::FailedToReadPassword(format!("{:?}", err)))
+}
+
+/// Ask for a new password twice.
+pub fn prompt_new(file: &str) -> Result<String, Error> {
+    if let Some(Some(supplied)) = NEW.get() {
+        return supplied.read();
+    }
+    prompt_twice(
+        &format!("New password for {}: ", file),
+        &format!("Confirm new password for {}: ", file),
+    )
+}
+
+/// Ask twice for the password of a file that was created but never saved,
+/// as any password opens it and the first save sets it.
+pub fn prompt_first(file: &str) -> Result<String, Error> {
+    if let Some(Some(supplied)) = CURRENT.get() {
+        return supplied.read();
+    }
+    prompt_twice(
+        &format!("{} has no password yet. Password for {}: ", file, file),
+        &format!("Confirm password for {}: ", file),
+    )
+}
+
+fn prompt_twice(prompt: &str, confirm_prompt: &str) -> Result<String, Error> {
+    let password = rpassword::prompt_password(prompt)
+        .map_err(|err| Error::FailedToReadPassword(format!("{:?}", err)))?;
+    if password.is_empty() {
+        return Err(Error::FailedToReadPassword("Empty password".to_string()));
+    }
+    let confirm = rpassword::prompt_password(confirm_prompt)
+        .map_err(|err| Error::FailedToReadPassword(format!("{:?}", err)))?;
+    if confirm != password {
+        return Err(Error::PasswordMismatch);
+    }
+    Ok(password)
+}
+
+/// The key of a file held by the agent, if it is running.
+pub fn cached_key(file: &str) -> Option<Key> {
+    #[cfg(unix)]
+    return safe_writing_rs::agent::get_key(file);
+    #[cfg(not(unix))]
+    return None;
+}
+
+/// Hand the key of a file to the agent, if it is running.
+pub fn cache_key(file: &str, key: &Key) {
+    #[cfg(unix)]
+    safe_writing_rs::agent::put_key(file, key);
+}
@@ -0,0 +1,97 @@
+use clap::Args;
+use safe_writing_rs::{
+    error::{Error, ErrorKind},
+    storage::Storage,
+    verify::verify_file,
+};
+use serde::Serialize;
+
+use crate::password;
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    /// Files to check, all by default
+    files: Vec<String>,
+    /// Only check the layout of the files, without asking for a password
+    #[arg(long)]
+    no_password: bool,
+    /// Ask for the password of each file, instead of one for all
+    #[arg(long, conflicts_with = "no_password")]
+    prompt_each: bool,
+}
+
+#[derive(Serialize)]
+struct Report {
+    file: String,
+    ok: bool,
+    decrypted: bool,
+    findings: Vec<String>,
+    #[serde(skip)]
+    kind: Option<ErrorKind>,
+}
+
+/// Check the files and fail if any of them has a problem.
+pub fn verify(storage: &dyn Storage, args: VerifyArgs, json: bool) -> Result<(), Error> {
+    let files = if args.files.is_empty() {
+        storage.list()?
+    } else {
+        args.files
+    };
+    let shared_password = if args.prompt_each || args.no_password {
+        None
+    } else {
+        Some(password::prompt("Password: ")?)
+    };
+
+    let mut reports = vec![];
+    for file in files {
+        let password = match &shared_password {
+            Some(password) => Some(password.clone()),
+            None if args.prompt_each => {
+                Some(password::prompt(&format!("Password for {}: ", file))?)
+            }
+            None => None,
+        };
+        let report = verify_file(storage, &file, password.as_deref());
+        reports.push(Report {
+            ok: report.is_ok(),
+            decrypted: report.decrypted,
+            findings: report.findings.iter().map(|f| f.to_string()).collect(),
+            kind: report.kind(),
+            file: report.file,
+        });
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports).unwrap());
+    } else {
+        for report in reports.iter() {
+            if report.findings.is_empty() {
+                let checked = if report.decrypted {
+                    ""
+                } else {
+                    " (not decrypted)"
+                };
+                println!("{}: ok{}", report.file, checked);
+            }
+            for finding in report.findings.iter() {
+                println!("{}: {}", report.file, finding);
+            }
+        }
+    }
+    // A wrong password fails every file the same way, so exit with its code
+    // rather than claim the files are damaged
+    let mut kinds = reports.iter().filter_map(|report| report.kind);
+    match kinds.next() {
+        None => Ok(()),
+        Some(first) => {
+            let kind = if kinds.all(|kind| kind == first) {
+                first
+            } else {
+                ErrorKind::Corrupted
+            };
+            let failed = reports.iter().filter(|report| !report.ok).count();
+            Err(Error::VerificationFailed(failed, kind))
+        }
+    }
+}
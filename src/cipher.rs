@@ -8,23 +8,33 @@ use crate::{
     error::Error,
 };
 
-pub fn key_derive(password: &str) -> [u8; 16] {
+/// The AES key derived from a password. Anyone holding it can read and write
+/// the files encrypted with that password.
+pub type Key = [u8; 16];
+
+pub fn key_derive(password: &str) -> Key {
     let mut out = [0u8; 16]; // We will use 128 bits key
     pbkdf2::pbkdf2_hmac::<sha2::Sha256>(password.as_bytes(), b"safe_write", 100, &mut out);
     out
 }
 
 pub fn encrypt(password: &str, data: &PlainText) -> String {
-    let data = data.encode();
-    let key = key_derive(password);
+    encrypt_with_key(&key_derive(password), data)
+}
 
+pub fn encrypt_with_key(key: &Key, data: &PlainText) -> String {
+    encrypt_bytes_with_key(key, &data.encode())
+}
+
+/// Encrypt anything in the layout of a .safe file: the IV, the ciphertext
+/// and the MAC, a line each.
+pub fn encrypt_bytes_with_key(key: &Key, data: &[u8]) -> String {
     let mut iv = [0u8; 16];
     StdRng::from_os_rng().fill_bytes(&mut iv);
 
-    let encrypted = cbc::Encryptor::<aes::Aes128>::new(&key.into(), &iv.into())
-        .encrypt_padded_vec_mut::<Pkcs7>(&data);
-    let mut mac =
-        Hmac::<sha2::Sha256>::new_from_slice(&key).expect("HMAC can take key of any size");
+    let encrypted = cbc::Encryptor::<aes::Aes128>::new(key.into(), &iv.into())
+        .encrypt_padded_vec_mut::<Pkcs7>(data);
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(key).expect("HMAC can take key of any size");
     mac.update(encrypted.as_slice());
 
     base64_encode(iv)
@@ -35,75 +45,44 @@ pub fn encrypt(password: &str, data: &PlainText) -> String {
 }
 
 pub fn decrypt(password: &str, iv: &str, data: &str, mac: &str) -> Result<PlainText, Error> {
-    let key = key_derive(password);
+    decrypt_with_key(&key_derive(password), iv, data, mac)
+}
+
+pub fn decrypt_with_key(key: &Key, iv: &str, data: &str, mac: &str) -> Result<PlainText, Error> {
+    parse_plaintext(decrypt_to_bytes(key, iv, data, mac)?)
+}
+
+/// Check the MAC and decrypt, without parsing the result.
+pub fn decrypt_to_bytes(key: &Key, iv: &str, data: &str, mac: &str) -> Result<Vec<u8>, Error> {
     let iv = base64_decode_to_bytes(iv)?;
     let data = base64_decode_to_bytes(data)?;
     let mac = base64_decode_to_bytes(mac)?;
     let mut mac_calculated =
-        Hmac::<sha2::Sha256>::new_from_slice(&key).expect("HMAC can take key of any size");
+        Hmac::<sha2::Sha256>::new_from_slice(key).expect("HMAC can take key of any size");
     mac_calculated.update(data.as_slice());
     mac_calculated
         .verify_slice(&mac)
         .map_err(|err| Error::MacFail(err))?;
 
-    let (plaintext, images) = cbc::Decryptor::<aes::Aes128>::new(&key.into(), iv.as_slice().into())
+    if iv.len() != 16 {
+        return Err(Error::DecryptionFail);
+    }
+
+    cbc::Decryptor::<aes::Aes128>::new(key.into(), iv.as_slice().into())
         .decrypt_padded_vec_mut::<Pkcs7>(&data)
         .map_err(|_| Error::DecryptionFail)
-        .and_then(|s| {
-            let (plaintext, images) = if let Some((i, _)) = s
-                .iter()
-                .enumerate()
-                .filter(|(_, b)| *b == &IMAGE_SEP)
-                .next()
-            {
-                let (plaintext, images) = s.split_at(i);
-                let images = &images[1..];
-                (plaintext.to_vec(), images.to_vec())
-            } else {
-                (s, vec![])
-            };
-            let plaintext = String::from_utf8(plaintext).map_err(|_| Error::InvalidUTF8)?;
-            Ok((plaintext, images))
-        })?;
-
-    let images = if images.is_empty() {
-        vec![]
-    } else {
-        // The images are encoded as follows:
-        // 1. Number of images, encoded as u32
-        // 2. For each image, the image data is encoded as:
-        //    2.1 Size of the image, encoded as u32
-        //    2.2 The image data (in png format)
-        if images.len() < size_of::<u32>() {
-            return Err(Error::InvalidImageFormat);
-        }
-        let num_images = u32::from_le_bytes(
-            images[0..4]
-                .try_into()
-                .map_err(|_| Error::InvalidImageFormat)?,
-        );
-
-        let mut splitted_images = Vec::with_capacity(num_images as usize);
+}
 
-        let mut images = &images[4..];
-        for _ in 0..num_images {
-            if images.len() < size_of::<u32>() {
-                return Err(Error::InvalidImageFormat);
-            }
-            let image_size = u32::from_le_bytes(
-                images[0..4]
-                    .try_into()
-                    .map_err(|_| Error::InvalidImageFormat)?,
-            );
-            images = &images[4..];
-            if images.len() < image_size as usize {
-                return Err(Error::InvalidImageFormat);
-            }
-            splitted_images.push(images[0..image_size as usize].to_vec());
-            images = &images[image_size as usize..];
-        }
-        splitted_images
+/// Parse the decrypted content of a .safe file: the passages, optionally
+/// followed by IMAGE_SEP and the image section.
+pub fn parse_plaintext(data: Vec<u8>) -> Result<PlainText, Error> {
+    let (plaintext, images) = if let Some(i) = data.iter().position(|b| *b == IMAGE_SEP) {
+        let (plaintext, images) = data.split_at(i);
+        (plaintext.to_vec(), parse_images(&images[1..])?)
+    } else {
+        (data, vec![])
     };
+    let plaintext = String::from_utf8(plaintext).map_err(|_| Error::InvalidUTF8)?;
 
     let plaintexts: Vec<_> = plaintext.split(":").collect();
     if plaintexts.len() < 2 {
@@ -113,7 +92,7 @@ pub fn decrypt(password: &str, iv: &str, data: &str, mac: &str) -> Result<PlainT
     // let font_size = plaintexts[1];
 
     if plaintext_encodings.is_empty() {
-        return Ok(PlainText::empty());
+        return Ok(PlainText::new(0, vec![], images));
     };
 
     let plaintext_encodings: Vec<_> = plaintext_encodings.split("|").collect();
@@ -128,13 +107,60 @@ pub fn decrypt(password: &str, iv: &str, data: &str, mac: &str) -> Result<PlainT
             }
             let title = contents[0];
             let content = contents[1];
-            Ok(Passage::new(
-                i,
-                base64_decode(title)?,
-                base64_decode(content)?,
-            ))
+            let passage = Passage::new(i, base64_decode(title)?, base64_decode(content)?);
+            match contents.get(2) {
+                Some(metadata) => Ok(passage.with_metadata(
+                    serde_json::from_str(&base64_decode(metadata)?)
+                        .map_err(|_| Error::InvalidPlaintextFormat)?,
+                )),
+                None => Ok(passage),
+            }
         })
         .collect::<Result<Vec<_>, _>>()?;
 
     Ok(PlainText::new(passages.len(), passages, images))
 }
+
+/// Parse the image section of a .safe file, i.e., what follows IMAGE_SEP.
+pub fn parse_images(images: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    if images.is_empty() {
+        return Ok(vec![]);
+    }
+    // The images are encoded as follows:
+    // 1. Number of images, encoded as u32
+    // 2. For each image, the image data is encoded as:
+    //    2.1 Size of the image, encoded as u32
+    //    2.2 The image data (in png format)
+    if images.len() < size_of::<u32>() {
+        return Err(Error::InvalidImageFormat);
+    }
+    let num_images = u32::from_le_bytes(
+        images[0..4]
+            .try_into()
+            .map_err(|_| Error::InvalidImageFormat)?,
+    );
+
+    let mut images = &images[4..];
+    // Every image takes at least the four bytes of its size, so a corrupted
+    // count cannot make us allocate more than the data could hold
+    let mut splitted_images =
+        Vec::with_capacity((num_images as usize).min(images.len() / size_of::<u32>()));
+
+    for _ in 0..num_images {
+        if images.len() < size_of::<u32>() {
+            return Err(Error::InvalidImageFormat);
+        }
+        let image_size = u32::from_le_bytes(
+            images[0..4]
+                .try_into()
+                .map_err(|_| Error::InvalidImageFormat)?,
+        );
+        images = &images[4..];
+        if images.len() < image_size as usize {
+            return Err(Error::InvalidImageFormat);
+        }
+        splitted_images.push(images[0..image_size as usize].to_vec());
+        images = &images[image_size as usize..];
+    }
+    Ok(splitted_images)
+}
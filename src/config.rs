@@ -0,0 +1,134 @@
+use homedir::my_home;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::{
+    error::Error,
+    storage::{Storage, StorageConfig},
+};
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Config {
+    pub(crate) font_size: f32,
+    pub(crate) data_dir: String,
+    #[serde(default)]
+    pub(crate) storage: StorageConfig,
+    #[serde(default)]
+    pub(crate) auto_lock: AutoLockConfig,
+    #[serde(default)]
+    pub(crate) agent: AgentConfig,
+    #[serde(default)]
+    pub(crate) export: ExportConfig,
+}
+
+impl Config {
+    /// The directory holding config.toml: $SAFE_WRITING_CONFIG_DIR if set,
+    /// otherwise ~/.safe_writing
+    pub fn config_dir() -> PathBuf {
+        std::env::var("SAFE_WRITING_CONFIG_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| my_home().unwrap().unwrap().as_path().join(".safe_writing"))
+    }
+
+    /// Read config.toml, writing the default one the first time, and make
+    /// sure the data dir exists.
+    pub fn load() -> Result<Self, Error> {
+        let config_path = Self::config_dir();
+        Self::ensure_dir(&config_path)?;
+
+        let config_file = config_path.join("config.toml");
+        let config = if !config_file.exists() {
+            let config = Config {
+                font_size: 24.0,
+                data_dir: config_path.to_str().unwrap().to_owned(),
+                ..Default::default()
+            };
+            std::fs::write(&config_file, toml::to_string(&config).unwrap())
+                .map_err(|err| Error::FailedToWriteFile(format!("{:?}", err)))?;
+            config
+        } else {
+            let contents = std::fs::read_to_string(&config_file)
+                .map_err(|err| Error::FailedToOpenFile(format!("{:?}", err)))?;
+            toml::from_str(&contents).map_err(|err| Error::InvalidConfig(format!("{}", err)))?
+        };
+
+        Self::ensure_dir(&PathBuf::from(&config.data_dir))?;
+        Ok(config)
+    }
+
+    fn ensure_dir(path: &PathBuf) -> Result<(), Error> {
+        if !path.is_dir() {
+            if path.exists() {
+                return Err(Error::InvalidConfig(format!(
+                    "{} is not a directory",
+                    path.display()
+                )));
+            }
+            std::fs::create_dir_all(path)
+                .map_err(|err| Error::FailedToWriteFile(format!("{:?}", err)))?;
+        }
+        Ok(())
+    }
+
+    pub fn data_dir(&self) -> &String {
+        &self.data_dir
+    }
+
+    pub fn font_size(&self) -> f32 {
+        self.font_size
+    }
+
+    pub fn storage(&self) -> Box<dyn Storage> {
+        self.storage.open(&self.data_dir)
+    }
+
+    /// The size of the text in exported PDFs, in points.
+    pub fn pdf_font_size(&self) -> f32 {
+        self.export.pdf_font_size
+    }
+
+    /// How long the agent keeps a key.
+    pub fn agent_key_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.agent.key_timeout_seconds)
+    }
+}
+
+/// Locking the opened file by itself. All off unless turned on in
+/// config.toml, so that it does not start without being asked for.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct AutoLockConfig {
+    /// Lock the opened file after this many seconds without any input. Zero
+    /// disables it.
+    pub idle_seconds: u64,
+    pub on_focus_loss: bool,
+    pub on_suspend: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub(crate) struct AgentConfig {
+    pub(crate) key_timeout_seconds: u64,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            key_timeout_seconds: 900,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub(crate) struct ExportConfig {
+    pub(crate) pdf_font_size: f32,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            pdf_font_size: 12.0,
+        }
+    }
+}
@@ -8,3 +8,6 @@ pub const PASSAGE_LIST_BUTTON_HEIGHT: f32 = 24.0;
 pub const PASSAGE_LIST_WIDTH: f32 = 180.0;
 pub const FILE_LIST_SMALL_BUTTON_SIZE: f32 = 24.0;
 pub const PASSAGE_LIST_SMALL_BUTTON_SIZE: f32 = 24.0;
+
+/// The font the app shows text in, also embedded into exported documents.
+pub const LXGW_FONT: &[u8] = include_bytes!("../assets/LXGWWenKaiGB-Regular.ttf");
@@ -1,21 +1,76 @@
+use std::collections::{BTreeMap, HashSet};
+
+use sha2::Digest;
+
 use crate::{
-    cipher::{decrypt, encrypt},
+    cipher::{decrypt_with_key, encrypt_with_key, key_derive, Key},
     encode::base64_encode,
     error::Error,
 };
 
 pub(crate) const IMAGE_SEP: u8 = 0x88;
 
+/// The SHA256 hex digest that passages refer to an image by.
+pub fn image_digest(image: &[u8]) -> String {
+    format!("{:x}", {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(image);
+        hasher.finalize()
+    })
+}
+
+/// The line in a passage where the image is shown.
+pub fn image_placeholder(digest: &str) -> String {
+    format!("image!({})", digest)
+}
+
+/// The digest of the image, if the line is an image placeholder.
+pub fn image_reference(line: &str) -> Option<&str> {
+    let digest = line.strip_prefix("image!(")?.strip_suffix(')')?;
+    // SHA256 digest hex has length 64
+    if digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(digest)
+    } else {
+        None
+    }
+}
+
+/// Well-known keys of the metadata of a passage, as filled in by imports.
+/// Times are in RFC 3339.
+pub const METADATA_CREATED: &str = "created";
+pub const METADATA_MODIFIED: &str = "modified";
+/// Comma separated
+pub const METADATA_TAGS: &str = "tags";
+/// The ID of the note in the app it is imported from
+pub const METADATA_SOURCE_ID: &str = "source_id";
+/// The fields of the note in the app it is imported from, as a JSON object,
+/// so that exporting back to that app writes them as they were
+pub const METADATA_SOURCE_FIELDS: &str = "source_fields";
+
 #[derive(Debug, Clone)]
 pub struct Passage {
     id: usize,
     title: String,
     content: String,
+    metadata: BTreeMap<String, String>,
 }
 
 impl Passage {
     pub fn new(id: usize, title: String, content: String) -> Self {
-        Self { id, title, content }
+        Self {
+            id,
+            title,
+            content,
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_metadata(self, metadata: BTreeMap<String, String>) -> Self {
+        Self { metadata, ..self }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
     }
 
     pub fn title(&self) -> &String {
@@ -26,18 +81,57 @@ impl Passage {
         &self.content
     }
 
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    /// The metadata, if any, is a third part that older versions ignore.
     pub fn encode(&self) -> String {
         let title = base64_encode(self.title.as_bytes());
         let content = base64_encode(self.content.as_bytes());
-        title + "-" + &content
+        if self.metadata.is_empty() {
+            title + "-" + &content
+        } else {
+            let metadata = base64_encode(serde_json::to_string(&self.metadata).unwrap());
+            title + "-" + &content + "-" + &metadata
+        }
+    }
+}
+
+/// Addresses a passage either by its position in the file, or by the ID it
+/// keeps while passages are inserted, removed and moved around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassageRef {
+    Index(usize),
+    Id(usize),
+}
+
+impl From<usize> for PassageRef {
+    fn from(index: usize) -> Self {
+        PassageRef::Index(index)
     }
 }
 
+/// Reported by every edit made through the `PlainText` API once
+/// `record_changes` is called, so that a front end can refresh only what
+/// changed. Indices are the ones right after the edit is applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlainTextChange {
+    Inserted { id: usize, index: usize },
+    Removed { id: usize, index: usize },
+    Moved { id: usize, from: usize, to: usize },
+    TitleChanged { id: usize },
+    ContentChanged { id: usize },
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct PlainText {
     next_id: usize,
     content: Vec<Passage>,
     images: Vec<Vec<u8>>,
+    /// None until recording is asked for, so that edits nobody looks at are
+    /// not kept
+    changes: Option<Vec<PlainTextChange>>,
 }
 
 impl PlainText {
@@ -46,6 +140,7 @@ impl PlainText {
             next_id,
             content,
             images,
+            changes: None,
         }
     }
 
@@ -54,11 +149,18 @@ impl PlainText {
     }
 
     pub fn from_passages(content: Vec<Passage>) -> Self {
-        Self::new(0, content, vec![])
+        Self::from_passages_images(content, vec![])
     }
 
+    /// The passages are given fresh IDs, as the ones they carry may come from
+    /// different sources and collide.
     pub fn from_passages_images(content: Vec<Passage>, images: Vec<Vec<u8>>) -> Self {
-        Self::new(0, content, images)
+        let content = content
+            .into_iter()
+            .enumerate()
+            .map(|(id, p)| Passage { id, ..p })
+            .collect::<Vec<_>>();
+        Self::new(content.len(), content, images)
     }
 
     pub fn num_passages(&self) -> usize {
@@ -85,6 +187,8 @@ impl PlainText {
         self.content.get(index).map(|p| &p.content)
     }
 
+    /// Edits made through the returned reference are not reported by
+    /// `take_changes`.
     pub fn content_of_passage_mut(&mut self, index: usize) -> Option<&mut String> {
         self.content.get_mut(index).map(|p| &mut p.content)
     }
@@ -97,28 +201,127 @@ impl PlainText {
         &mut self.images
     }
 
+    /// Digests of the images shown by an `image!(...)` line in some passage.
+    pub fn image_references(&self) -> HashSet<&str> {
+        self.content
+            .iter()
+            .flat_map(|passage| passage.content.split('\n'))
+            .filter_map(image_reference)
+            .collect()
+    }
+
     pub fn id_of_passage(&self, index: usize) -> Option<usize> {
         self.content.get(index).map(|p| p.id)
     }
 
-    pub fn set_content(&mut self, index: usize, content: String) {
+    /// Resolve a passage reference to the current index of the passage.
+    pub fn index_of<P: Into<PassageRef>>(&self, passage: P) -> Result<usize, Error> {
+        match passage.into() {
+            PassageRef::Index(index) if index < self.content.len() => Ok(index),
+            PassageRef::Index(index) => Err(Error::PassageIndexOutOfRange(index)),
+            PassageRef::Id(id) => self
+                .content
+                .iter()
+                .position(|p| p.id == id)
+                .ok_or(Error::PassageIdNotFound(id)),
+        }
+    }
+
+    pub fn passage<P: Into<PassageRef>>(&self, passage: P) -> Result<&Passage, Error> {
+        Ok(&self.content[self.index_of(passage)?])
+    }
+
+    /// Keep the changes made from now on until they are taken.
+    pub fn record_changes(&mut self) {
+        self.changes.get_or_insert_with(Vec::new);
+    }
+
+    /// Changes reported since the last call, oldest first. Always empty when
+    /// not recording.
+    pub fn take_changes(&mut self) -> Vec<PlainTextChange> {
+        self.changes
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn changed(&mut self, change: PlainTextChange) {
+        if let Some(changes) = &mut self.changes {
+            changes.push(change);
+        }
+    }
+
+    pub fn set_content<P: Into<PassageRef>>(
+        &mut self,
+        passage: P,
+        content: String,
+    ) -> Result<(), Error> {
+        let index = self.index_of(passage)?;
         self.content[index].content = content;
+        self.changed(PlainTextChange::ContentChanged {
+            id: self.content[index].id,
+        });
+        Ok(())
     }
 
-    pub fn set_title(&mut self, index: usize, title: String) {
+    pub fn set_title<P: Into<PassageRef>>(
+        &mut self,
+        passage: P,
+        title: String,
+    ) -> Result<(), Error> {
+        let index = self.index_of(passage)?;
         self.content[index].title = title;
+        self.changed(PlainTextChange::TitleChanged {
+            id: self.content[index].id,
+        });
+        Ok(())
     }
 
     pub fn passages(&self) -> &Vec<Passage> {
         &self.content
     }
 
-    pub fn remove_passage(&mut self, index: usize) -> Passage {
-        self.content.remove(index)
+    pub fn remove_passage<P: Into<PassageRef>>(&mut self, passage: P) -> Result<Passage, Error> {
+        let index = self.index_of(passage)?;
+        let passage = self.content.remove(index);
+        self.changed(PlainTextChange::Removed {
+            id: passage.id,
+            index,
+        });
+        Ok(passage)
     }
 
+    /// Append the passages of another file. They get new IDs in this file.
     pub fn append_plaintext(&mut self, plaintext: &PlainText) {
-        self.content.extend(plaintext.content.clone());
+        for passage in plaintext.content.iter() {
+            self.append_passage(passage);
+        }
+    }
+
+    /// Append the passages at `indices` of another file, in that order. The
+    /// images they show are not copied.
+    pub fn append_passages(
+        &mut self,
+        plaintext: &PlainText,
+        indices: &[usize],
+    ) -> Result<(), Error> {
+        let passages = indices
+            .iter()
+            .map(|&index| plaintext.passage(index))
+            .collect::<Result<Vec<_>, _>>()?;
+        for passage in passages {
+            self.append_passage(passage);
+        }
+        Ok(())
+    }
+
+    fn append_passage(&mut self, passage: &Passage) {
+        self.insert_passage(
+            self.content.len(),
+            passage.title.clone(),
+            passage.content.clone(),
+        );
+        self.content.last_mut().unwrap().metadata = passage.metadata.clone();
     }
 
     pub fn bounded_index(&self, index: usize) -> usize {
@@ -162,31 +365,141 @@ impl PlainText {
         }
     }
 
-    pub fn insert_new_passage(&mut self, index: usize, title: String) {
-        self.content.insert(
-            index,
-            Passage {
-                id: self.next_id,
-                title,
-                content: "".to_string(),
-            },
-        );
+    fn insert_passage(&mut self, index: usize, title: String, content: String) -> usize {
+        let id = self.next_id;
+        self.content.insert(index, Passage::new(id, title, content));
         self.next_id += 1;
+        self.changed(PlainTextChange::Inserted { id, index });
+        id
+    }
+
+    /// Insert an empty passage so that it ends up at `index`, which may be
+    /// the number of passages to append at the end. Returns the ID of the new
+    /// passage.
+    pub fn insert_new_passage(&mut self, index: usize, title: String) -> Result<usize, Error> {
+        if index > self.content.len() {
+            return Err(Error::PassageIndexOutOfRange(index));
+        }
+        Ok(self.insert_passage(index, title, "".to_string()))
     }
 
-    pub fn swap(&mut self, a: usize, b: usize) {
+    pub fn swap<P: Into<PassageRef>, Q: Into<PassageRef>>(
+        &mut self,
+        a: P,
+        b: Q,
+    ) -> Result<(), Error> {
+        let a = self.index_of(a)?;
+        let b = self.index_of(b)?;
+        if a == b {
+            return Ok(());
+        }
         self.content.swap(a, b);
+        self.changed(PlainTextChange::Moved {
+            id: self.content[b].id,
+            from: a,
+            to: b,
+        });
+        self.changed(PlainTextChange::Moved {
+            id: self.content[a].id,
+            from: b,
+            to: a,
+        });
+        Ok(())
+    }
+
+    /// Move a passage so that it ends up at index `to`. The passages in
+    /// between shift by one.
+    pub fn move_passage<P: Into<PassageRef>>(
+        &mut self,
+        passage: P,
+        to: usize,
+    ) -> Result<(), Error> {
+        let from = self.index_of(passage)?;
+        if to >= self.content.len() {
+            return Err(Error::PassageIndexOutOfRange(to));
+        }
+        if from == to {
+            return Ok(());
+        }
+        let passage = self.content.remove(from);
+        let id = passage.id;
+        self.content.insert(to, passage);
+        self.changed(PlainTextChange::Moved { id, from, to });
+        Ok(())
+    }
+
+    /// Insert a copy of the passage right after it. Returns the ID of the
+    /// copy.
+    pub fn duplicate_passage<P: Into<PassageRef>>(&mut self, passage: P) -> Result<usize, Error> {
+        let index = self.index_of(passage)?;
+        let Passage {
+            title,
+            content,
+            metadata,
+            ..
+        } = self.content[index].clone();
+        let id = self.insert_passage(index + 1, title, content);
+        self.content[index + 1].metadata = metadata;
+        Ok(id)
+    }
+
+    /// Cut the content of the passage at byte offset `at`. The part after it
+    /// goes into a new passage right after this one, titled `new_title`.
+    /// Returns the ID of the new passage.
+    pub fn split_passage<P: Into<PassageRef>>(
+        &mut self,
+        passage: P,
+        at: usize,
+        new_title: String,
+    ) -> Result<usize, Error> {
+        let index = self.index_of(passage)?;
+        if !self.content[index].content.is_char_boundary(at) {
+            return Err(Error::InvalidSplitPosition(at));
+        }
+        let rest = self.content[index].content.split_off(at);
+        self.changed(PlainTextChange::ContentChanged {
+            id: self.content[index].id,
+        });
+        Ok(self.insert_passage(index + 1, new_title, rest))
+    }
+
+    /// Append the content of the next passage to this one and remove the next
+    /// passage. The contents are joined as they are, so that merging undoes a
+    /// split.
+    pub fn merge_with_next<P: Into<PassageRef>>(&mut self, passage: P) -> Result<(), Error> {
+        let index = self.index_of(passage)?;
+        if index + 1 >= self.content.len() {
+            return Err(Error::PassageIndexOutOfRange(index + 1));
+        }
+        let next = self.content.remove(index + 1);
+        self.content[index].content.push_str(&next.content);
+        self.changed(PlainTextChange::Removed {
+            id: next.id,
+            index: index + 1,
+        });
+        self.changed(PlainTextChange::ContentChanged {
+            id: self.content[index].id,
+        });
+        Ok(())
     }
 
     pub fn encrypt(&self, password: &str) -> String {
-        encrypt(password, self)
+        self.encrypt_with_key(&key_derive(password))
+    }
+
+    pub fn encrypt_with_key(&self, key: &Key) -> String {
+        encrypt_with_key(key, self)
     }
 
     pub fn decrypt(password: &str, ciphertext: &str) -> Result<Self, Error> {
+        Self::decrypt_with_key(&key_derive(password), ciphertext)
+    }
+
+    pub fn decrypt_with_key(key: &Key, ciphertext: &str) -> Result<Self, Error> {
         let ciphertext = ciphertext.split("\n").collect::<Vec<_>>();
         if ciphertext.len() < 3 {
             return Err(Error::DecryptionFail);
         }
-        decrypt(password, ciphertext[0], ciphertext[1], ciphertext[2])
+        decrypt_with_key(key, ciphertext[0], ciphertext[1], ciphertext[2])
     }
 }
@@ -1,4 +1,5 @@
 use hmac::digest::MacError;
+use serde::Serialize;
 
 #[derive(Debug)]
 pub enum Error {
@@ -10,4 +11,85 @@ pub enum Error {
     InvalidPlaintextFormat,
     FailedToParseJson(String),
     InvalidImageFormat,
+    FileNotFound(String),
+    FailedToWriteFile(String),
+    FailedToDeleteFile(String),
+    FailedToListFiles(String),
+    WebDav(String),
+    PassageIndexOutOfRange(usize),
+    PassageIdNotFound(usize),
+    InvalidSplitPosition(usize),
+    InvalidConfig(String),
+    PassageNotFound(String),
+    PasswordMismatch,
+    FailedToReadPassword(String),
+    NoSecureTempDir,
+    EditorFailed(String),
+    InvalidPattern(String),
+    /// How many files failed, and the kind of error they share, or
+    /// `Corrupted` if they differ
+    VerificationFailed(usize, ErrorKind),
+    Agent(String),
+    ExportFailed(String),
+    InvalidSafeNotes(String),
+    FailedToParseXml(String),
+    InvalidBackup(String),
+    InvalidFileName(String),
+    FileExists(String),
+    TaskPanicked(String),
+}
+
+/// What went wrong, coarse enough for scripts to branch on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    WrongPassword,
+    /// The file is damaged or not a .safe file
+    Corrupted,
+    /// A file or passage that does not exist
+    NotFound,
+    /// Reading or writing the storage, or talking to the agent, failed
+    Io,
+    Other,
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            // The MAC is checked before decrypting, so this is what a wrong
+            // password gives
+            Error::MacFail(_) => ErrorKind::WrongPassword,
+            Error::VerificationFailed(_, kind) => *kind,
+            Error::Base64DecodeFail
+            | Error::DecryptionFail
+            | Error::InvalidUTF8
+            | Error::InvalidPlaintextFormat
+            | Error::FailedToParseJson(_)
+            | Error::InvalidImageFormat
+            | Error::InvalidSafeNotes(_)
+            | Error::FailedToParseXml(_)
+            | Error::InvalidBackup(_) => ErrorKind::Corrupted,
+            Error::FileNotFound(_)
+            | Error::PassageNotFound(_)
+            | Error::PassageIndexOutOfRange(_)
+            | Error::PassageIdNotFound(_) => ErrorKind::NotFound,
+            Error::FailedToOpenFile(_)
+            | Error::FailedToWriteFile(_)
+            | Error::FailedToDeleteFile(_)
+            | Error::FailedToListFiles(_)
+            | Error::WebDav(_)
+            | Error::NoSecureTempDir
+            | Error::Agent(_) => ErrorKind::Io,
+            Error::InvalidSplitPosition(_)
+            | Error::InvalidConfig(_)
+            | Error::PasswordMismatch
+            | Error::FailedToReadPassword(_)
+            | Error::EditorFailed(_)
+            | Error::InvalidPattern(_)
+            | Error::ExportFailed(_)
+            | Error::InvalidFileName(_)
+            | Error::FileExists(_)
+            | Error::TaskPanicked(_) => ErrorKind::Other,
+        }
+    }
 }
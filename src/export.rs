@@ -0,0 +1,101 @@
+//! Writing the content of a file out of the vault in other formats.
+
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    data_structures::{image_digest, Passage, PlainText},
+    error::Error,
+};
+
+mod epub;
+mod html;
+mod markdown;
+mod pdf;
+
+pub use epub::export_epub;
+pub use html::export_html;
+pub use markdown::export_markdown;
+pub use pdf::export_pdf;
+
+/// The passages at `indices`, in the order given.
+fn selected_passages<'a>(
+    plaintext: &'a PlainText,
+    indices: &[usize],
+) -> Result<Vec<&'a Passage>, Error> {
+    indices
+        .iter()
+        .map(|&index| {
+            plaintext
+                .passages()
+                .get(index)
+                .ok_or(Error::PassageIndexOutOfRange(index))
+        })
+        .collect()
+}
+
+/// The images of the file by their digests, as the placeholders refer to
+/// them.
+fn images_by_digest(plaintext: &PlainText) -> HashMap<String, &[u8]> {
+    plaintext
+        .images()
+        .iter()
+        .map(|image| (image_digest(image), image.as_slice()))
+        .collect()
+}
+
+/// The MIME type of an image, PNG if it is not recognized.
+fn image_mime_type(image: &[u8]) -> &'static str {
+    image::guess_format(image)
+        .map(|format| format.to_mime_type())
+        .unwrap_or("image/png")
+}
+
+/// Text put into HTML or XHTML, as an element or an attribute value.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A title usable as a file name on all platforms.
+fn file_name_of(title: &str) -> String {
+    let name = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    let name = name.trim().trim_matches('.');
+    if name.is_empty() {
+        "Untitled".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+fn write_file(path: &Path, data: &[u8]) -> Result<(), Error> {
+    std::fs::write(path, data).map_err(|err| {
+        Error::FailedToWriteFile(format!("Failed to write {}: {:?}", path.display(), err))
+    })
+}
+
+/// Create the directory to export into. It must not have anything in it, so
+/// that nothing is overwritten.
+fn create_empty_dir(dir: &Path) -> Result<(), Error> {
+    let map_err = |err: std::io::Error| {
+        Error::FailedToWriteFile(format!("Failed to create {}: {:?}", dir.display(), err))
+    };
+    if dir.exists() {
+        if std::fs::read_dir(dir).map_err(map_err)?.next().is_some() {
+            return Err(Error::FailedToWriteFile(format!(
+                "{} is not empty",
+                dir.display()
+            )));
+        }
+        return Ok(());
+    }
+    std::fs::create_dir_all(dir).map_err(map_err)
+}
@@ -0,0 +1,233 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Write},
+    path::Path,
+};
+
+use sha2::{Digest, Sha256};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use super::{escape, image_mime_type, images_by_digest, selected_passages, write_file};
+use crate::{
+    consts::LXGW_FONT,
+    data_structures::{image_reference, PlainText},
+    error::Error,
+};
+
+/// Write the passages at `indices` into an EPUB 3 book at `path`, a chapter
+/// for each of them, with their titles as the table of contents. The images
+/// the chapters show are packaged with them, and so is the font if
+/// `embed_font` is set.
+pub fn export_epub(
+    plaintext: &PlainText,
+    title: &str,
+    indices: &[usize],
+    embed_font: bool,
+    path: &Path,
+) -> Result<(), Error> {
+    let passages = selected_passages(plaintext, indices)?;
+    let images = images_by_digest(plaintext);
+
+    let mut book = Book::default();
+    // Only the images shown, each once, in the order they first appear
+    let mut image_files = HashMap::new();
+    let mut chapters = vec![];
+    for (i, passage) in passages.iter().enumerate() {
+        let file = format!("chapter-{}.xhtml", i + 1);
+        let mut body = String::new();
+        for line in passage.content().split('\n') {
+            match image_reference(line).and_then(|digest| Some((digest, images.get(digest)?))) {
+                Some((digest, image)) => {
+                    let next = image_files.len() + 1;
+                    let image_file = image_files
+                        .entry(digest.to_string())
+                        .or_insert_with(|| {
+                            let mime_type = image_mime_type(image);
+                            let name = format!(
+                                "images/image-{}.{}",
+                                next,
+                                mime_type.trim_start_matches("image/")
+                            );
+                            book.resource(&name, mime_type, image);
+                            name
+                        })
+                        .clone();
+                    body.push_str(&format!("<p><img src=\"{}\" alt=\"\"/></p>\n", image_file));
+                }
+                None if line.trim().is_empty() => {}
+                None => body.push_str(&format!("<p>{}</p>\n", escape(line))),
+            }
+        }
+        book.chapter(
+            &file,
+            xhtml(
+                passage.title(),
+                &format!("<h1>{}</h1>\n{}", escape(passage.title()), body),
+            ),
+        );
+        chapters.push((file, passage.title()));
+    }
+
+    let mut style = STYLE.to_string();
+    if embed_font {
+        book.resource("fonts/LXGW.ttf", "font/ttf", LXGW_FONT);
+        style = format!(
+            "@font-face {{ font-family: \"LXGW\"; src: url(fonts/LXGW.ttf); }}\nbody {{ font-family: \"LXGW\", serif; }}\n{}",
+            style
+        );
+    }
+    book.resource("style.css", "text/css", style.as_bytes());
+
+    let nav = chapters
+        .iter()
+        .map(|(file, title)| format!("<li><a href=\"{}\">{}</a></li>\n", file, escape(title)))
+        .collect::<String>();
+    let nav = xhtml(
+        title,
+        &format!(
+            "<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n<ol>\n{}</ol>\n</nav>\n",
+            escape(title),
+            nav
+        ),
+    );
+
+    write_file(path, &book.finish(title, &nav)?)
+}
+
+const STYLE: &str = "p { margin: 0 0 0.8em; }
+img { max-width: 100%; }
+";
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+<rootfiles>
+<rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+</rootfiles>
+</container>
+"#;
+
+/// The files of the book under `OEBPS`, in the order of the manifest.
+#[derive(Default)]
+struct Book {
+    /// Path, media type, data
+    resources: Vec<(String, &'static str, Vec<u8>)>,
+    chapters: Vec<String>,
+}
+
+impl Book {
+    fn resource(&mut self, name: &str, media_type: &'static str, data: &[u8]) {
+        self.resources
+            .push((name.to_string(), media_type, data.to_vec()));
+    }
+
+    fn chapter(&mut self, name: &str, xhtml: String) {
+        self.resource(name, "application/xhtml+xml", xhtml.as_bytes());
+        self.chapters.push(name.to_string());
+    }
+
+    fn finish(self, title: &str, nav: &str) -> Result<Vec<u8>, Error> {
+        let mut hasher = Sha256::new();
+        for (name, _, data) in &self.resources {
+            hasher.update(name.as_bytes());
+            hasher.update(data);
+        }
+        let identifier = format!("urn:sha256:{:x}", hasher.finalize());
+
+        let mut manifest = String::from(
+            "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+        );
+        for (i, (name, media_type, _)) in self.resources.iter().enumerate() {
+            manifest.push_str(&format!(
+                "<item id=\"item-{}\" href=\"{}\" media-type=\"{}\"/>\n",
+                i + 1,
+                name,
+                media_type
+            ));
+        }
+        let spine = self
+            .chapters
+            .iter()
+            .map(|chapter| {
+                let i = self
+                    .resources
+                    .iter()
+                    .position(|(name, _, _)| name == chapter)
+                    .unwrap();
+                format!("<itemref idref=\"item-{}\"/>\n", i + 1)
+            })
+            .collect::<String>();
+        let package = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+<metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+<dc:identifier id="id">{}</dc:identifier>
+<dc:title>{}</dc:title>
+<dc:language>zh</dc:language>
+<meta property="dcterms:modified">{}</meta>
+</metadata>
+<manifest>
+{}</manifest>
+<spine>
+{}</spine>
+</package>
+"#,
+            identifier,
+            escape(title),
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+            manifest,
+            spine
+        );
+
+        let map_err = |err: zip::result::ZipError| {
+            Error::FailedToWriteFile(format!("Failed to write EPUB: {:?}", err))
+        };
+        let map_io_err = |err: std::io::Error| {
+            Error::FailedToWriteFile(format!("Failed to write EPUB: {:?}", err))
+        };
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        // Must come first and be stored, as readers look for it at a fixed
+        // offset
+        zip.start_file(
+            "mimetype",
+            SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+        )
+        .map_err(map_err)?;
+        zip.write_all(b"application/epub+zip").map_err(map_io_err)?;
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let files = [
+            ("META-INF/container.xml".to_string(), CONTAINER.as_bytes()),
+            ("OEBPS/content.opf".to_string(), package.as_bytes()),
+            ("OEBPS/nav.xhtml".to_string(), nav.as_bytes()),
+        ]
+        .into_iter()
+        .chain(
+            self.resources
+                .iter()
+                .map(|(name, _, data)| (format!("OEBPS/{}", name), data.as_slice())),
+        );
+        for (name, data) in files {
+            zip.start_file(name, options).map_err(map_err)?;
+            zip.write_all(data).map_err(map_io_err)?;
+        }
+        Ok(zip.finish().map_err(map_err)?.into_inner())
+    }
+}
+
+fn xhtml(title: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+<meta charset="utf-8"/>
+<title>{}</title>
+<link rel="stylesheet" type="text/css" href="style.css"/>
+</head>
+<body>
+{}</body>
+</html>
+"#,
+        escape(title),
+        body
+    )
+}
@@ -0,0 +1,82 @@
+use std::{collections::HashMap, path::Path};
+
+use super::{escape, image_mime_type, images_by_digest, selected_passages, write_file};
+use crate::{
+    consts::LXGW_FONT,
+    data_structures::{image_reference, PlainText},
+    encode::base64_encode,
+    error::Error,
+};
+
+/// Write the passages at `indices` into one HTML document at `path`, with a
+/// table of contents linking to a section for each of them. Images and the
+/// font are embedded as data URIs, so the document needs nothing else to be
+/// shown or printed.
+pub fn export_html(
+    plaintext: &PlainText,
+    title: &str,
+    indices: &[usize],
+    path: &Path,
+) -> Result<(), Error> {
+    let passages = selected_passages(plaintext, indices)?;
+    let images = images_by_digest(plaintext);
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape(title)));
+    html.push_str(&format!(
+        "<style>\n@font-face {{ font-family: \"LXGW\"; src: url(data:font/ttf;base64,{}); }}\n{}</style>\n",
+        base64_encode(LXGW_FONT),
+        STYLE
+    ));
+    html.push_str("</head>\n<body>\n");
+    html.push_str(&format!("<h1>{}</h1>\n", escape(title)));
+
+    html.push_str("<nav>\n<ol>\n");
+    for (i, passage) in passages.iter().enumerate() {
+        html.push_str(&format!(
+            "<li><a href=\"#passage-{}\">{}</a></li>\n",
+            i + 1,
+            escape(passage.title())
+        ));
+    }
+    html.push_str("</ol>\n</nav>\n");
+
+    for (i, passage) in passages.iter().enumerate() {
+        html.push_str(&format!(
+            "<section id=\"passage-{}\">\n<h2>{}</h2>\n",
+            i + 1,
+            escape(passage.title())
+        ));
+        html.push_str(&to_html(passage.content(), &images));
+        html.push_str("</section>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+
+    write_file(path, html.as_bytes())
+}
+
+const STYLE: &str = "body { font-family: \"LXGW\", serif; max-width: 40em; margin: 2em auto; padding: 0 1em; line-height: 1.8; }
+section { page-break-before: always; }
+p { margin: 0 0 0.8em; white-space: pre-wrap; }
+img { max-width: 100%; }
+";
+
+/// A paragraph for every line, as the editor shows them, and the image
+/// placeholders as the images. A placeholder of an image not in the file is
+/// kept as text.
+fn to_html(content: &str, images: &HashMap<String, &[u8]>) -> String {
+    let mut html = String::new();
+    for line in content.split('\n') {
+        match image_reference(line).and_then(|digest| images.get(digest)) {
+            Some(image) => html.push_str(&format!(
+                "<p><img src=\"data:{};base64,{}\"></p>\n",
+                image_mime_type(image),
+                base64_encode(image)
+            )),
+            None if line.trim().is_empty() => {}
+            None => html.push_str(&format!("<p>{}</p>\n", escape(line))),
+        }
+    }
+    html
+}
@@ -0,0 +1,53 @@
+use std::path::Path;
+
+use super::{create_empty_dir, file_name_of, write_file};
+use crate::{
+    data_structures::{image_digest, image_reference, PlainText},
+    error::Error,
+};
+
+/// Write every passage into `dir` as a Markdown file, titled by a heading
+/// and named by its position and title. The images go into `dir/images`,
+/// named by their digests, and the `image!(<digest>)` lines become links to
+/// them. `dir` is created, and must be empty if it exists.
+pub fn export_markdown(plaintext: &PlainText, dir: &Path) -> Result<(), Error> {
+    create_empty_dir(dir)?;
+    if plaintext.num_images() > 0 {
+        let images_dir = dir.join("images");
+        create_empty_dir(&images_dir)?;
+        for image in plaintext.images() {
+            write_file(
+                &images_dir.join(format!("{}.png", image_digest(image))),
+                image,
+            )?;
+        }
+    }
+
+    // Wide enough that the files sort in the order of the passages
+    let width = plaintext.num_passages().to_string().len().max(2);
+    for (i, passage) in plaintext.passages().iter().enumerate() {
+        let name = format!(
+            "{:0width$} {}.md",
+            i + 1,
+            file_name_of(passage.title()),
+            width = width
+        );
+        write_file(
+            &dir.join(name),
+            to_markdown(passage.title(), passage.content()).as_bytes(),
+        )?;
+    }
+    Ok(())
+}
+
+fn to_markdown(title: &str, content: &str) -> String {
+    let body = content
+        .split('\n')
+        .map(|line| match image_reference(line) {
+            Some(digest) => format!("![](images/{}.png)", digest),
+            None => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("# {}\n\n{}\n", title, body.trim_end())
+}
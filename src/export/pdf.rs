@@ -0,0 +1,267 @@
+use std::{io::Cursor, path::Path};
+
+use printpdf::{
+    ColorBits, ColorSpace, Image, ImageTransform, ImageXObject, IndirectFontRef, Mm, PdfDocument,
+    PdfDocumentReference, PdfLayerReference, PdfPageIndex, Pt, Px,
+};
+use ttf_parser::Face;
+
+use super::{images_by_digest, selected_passages, write_file};
+use crate::{
+    consts::LXGW_FONT,
+    data_structures::{image_reference, PlainText},
+    error::Error,
+};
+
+/// A4, in points
+const PAGE_WIDTH: f32 = 595.28;
+const PAGE_HEIGHT: f32 = 841.89;
+const MARGIN: f32 = 56.7;
+const LINE_SPACING: f32 = 1.6;
+
+/// Write the passages at `indices` into a PDF at `path`, each from a new
+/// page under its title, in the bundled font at `font_size` points. Lines
+/// are wrapped to the page, between any two CJK characters or at spaces
+/// otherwise, and images are scaled to the width of the page.
+pub fn export_pdf(
+    plaintext: &PlainText,
+    title: &str,
+    indices: &[usize],
+    font_size: f32,
+    path: &Path,
+) -> Result<(), Error> {
+    let passages = selected_passages(plaintext, indices)?;
+    let images = images_by_digest(plaintext);
+    let face = Face::parse(LXGW_FONT, 0)
+        .map_err(|err| Error::ExportFailed(format!("Failed to read the font: {}", err)))?;
+
+    let (doc, page, layer) = PdfDocument::new(
+        title,
+        Mm::from(Pt(PAGE_WIDTH)),
+        Mm::from(Pt(PAGE_HEIGHT)),
+        "Layer 1",
+    );
+    let font = doc
+        .add_external_font(Cursor::new(LXGW_FONT))
+        .map_err(|err| Error::ExportFailed(format!("Failed to embed the font: {:?}", err)))?;
+    let mut pages = Pages {
+        layer: doc.get_page(page).get_layer(layer),
+        doc,
+        font,
+        face,
+        y: PAGE_HEIGHT - MARGIN,
+        empty: true,
+    };
+
+    for passage in passages {
+        if !pages.empty {
+            pages.new_page();
+        }
+        pages
+            .doc
+            .add_bookmark(passage.title().as_str(), pages.page_index());
+        pages.text(passage.title(), font_size * 1.5);
+        pages.skip(font_size);
+        for line in passage.content().split('\n') {
+            let image = image_reference(line)
+                .and_then(|digest| images.get(digest))
+                .and_then(|image| image::load_from_memory(image).ok());
+            match image {
+                Some(image) => pages.image(&image),
+                None if line.trim().is_empty() => pages.skip(font_size * LINE_SPACING),
+                None => pages.text(line, font_size),
+            }
+        }
+    }
+
+    let bytes = pages
+        .doc
+        .save_to_bytes()
+        .map_err(|err| Error::ExportFailed(format!("Failed to make the PDF: {:?}", err)))?;
+    write_file(path, &bytes)
+}
+
+/// Where the next line goes, starting new pages as they fill up.
+struct Pages<'a> {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    font: IndirectFontRef,
+    face: Face<'a>,
+    /// From the bottom of the page, in points
+    y: f32,
+    /// Nothing is on the current page yet
+    empty: bool,
+}
+
+impl Pages<'_> {
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(
+            Mm::from(Pt(PAGE_WIDTH)),
+            Mm::from(Pt(PAGE_HEIGHT)),
+            "Layer 1",
+        );
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = PAGE_HEIGHT - MARGIN;
+        self.empty = true;
+    }
+
+    fn page_index(&self) -> PdfPageIndex {
+        self.layer.page
+    }
+
+    /// Make room for something this tall, on a new page if it does not fit
+    /// on this one.
+    fn reserve(&mut self, height: f32) {
+        if self.y - height < MARGIN && !self.empty {
+            self.new_page();
+        }
+        self.y -= height;
+        self.empty = false;
+    }
+
+    fn skip(&mut self, height: f32) {
+        // Blank space at the top of a page is dropped
+        if !self.empty {
+            self.y -= height;
+            if self.y < MARGIN {
+                self.new_page();
+            }
+        }
+    }
+
+    fn text(&mut self, text: &str, font_size: f32) {
+        let lines = wrap(text, PAGE_WIDTH - 2.0 * MARGIN, |c| {
+            self.char_width(c, font_size)
+        });
+        for line in lines {
+            self.reserve(font_size * LINE_SPACING);
+            self.layer.use_text(
+                line,
+                font_size,
+                Mm::from(Pt(MARGIN)),
+                // The baseline, leaving the rest of the spacing above
+                Mm::from(Pt(self.y + font_size * (LINE_SPACING - 1.0) / 2.0)),
+                &self.font,
+            );
+        }
+    }
+
+    fn char_width(&self, c: char, font_size: f32) -> f32 {
+        let advance = self
+            .face
+            .glyph_index(c)
+            .and_then(|glyph| self.face.glyph_hor_advance(glyph))
+            .map(|advance| advance as f32 / self.face.units_per_em() as f32);
+        let fallback = if is_wide(c) { 1.0 } else { 0.5 };
+        advance.unwrap_or(fallback) * font_size
+    }
+
+    /// As wide as the text, or smaller to fit in a page. Transparent parts
+    /// are shown on white.
+    fn image(&mut self, image: &image::DynamicImage) {
+        let (width_px, height_px) = (image.width(), image.height());
+        if width_px == 0 || height_px == 0 {
+            return;
+        }
+        let max_width = PAGE_WIDTH - 2.0 * MARGIN;
+        let max_height = PAGE_HEIGHT - 2.0 * MARGIN;
+        let scale = (max_width / width_px as f32).min(max_height / height_px as f32);
+        let (width, height) = (width_px as f32 * scale, height_px as f32 * scale);
+
+        let pixels = image
+            .to_rgba8()
+            .pixels()
+            .flat_map(|pixel| {
+                let [r, g, b, a] = pixel.0;
+                let on_white = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+                [on_white(r), on_white(g), on_white(b)]
+            })
+            .collect();
+        self.reserve(height);
+        Image::from(ImageXObject {
+            width: Px(width_px as usize),
+            height: Px(height_px as usize),
+            color_space: ColorSpace::Rgb,
+            bits_per_component: ColorBits::Bit8,
+            interpolate: true,
+            image_data: pixels,
+            image_filter: None,
+            smask: None,
+            clipping_bbox: None,
+        })
+        .add_to_layer(
+            self.layer.clone(),
+            ImageTransform {
+                translate_x: Some(Mm::from(Pt(MARGIN + (max_width - width) / 2.0))),
+                translate_y: Some(Mm::from(Pt(self.y))),
+                // A pixel is 72 / dpi points
+                dpi: Some(72.0 / scale),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+/// Break a line into lines no wider than `max_width`. CJK text may break
+/// between any two characters, other text only at spaces unless a word is
+/// too long for a line. Closing punctuation never starts a line.
+fn wrap(text: &str, max_width: f32, char_width: impl Fn(char) -> f32) -> Vec<String> {
+    let mut tokens: Vec<String> = vec![];
+    for c in text.chars() {
+        let glue = match tokens.last().and_then(|last| last.chars().last()) {
+            None => false,
+            Some(last) if c.is_whitespace() => last.is_whitespace(),
+            Some(last) if last.is_whitespace() => false,
+            Some(_) if is_closing(c) => true,
+            Some(last) => !is_wide(c) && !is_wide(last),
+        };
+        match tokens.last_mut() {
+            Some(token) if glue => token.push(c),
+            _ => tokens.push(c.to_string()),
+        }
+    }
+
+    let width = |s: &str| s.chars().map(&char_width).sum::<f32>();
+    let mut lines = vec![];
+    let mut line = String::new();
+    for token in tokens {
+        if width(&line) + width(&token) > max_width && !line.trim().is_empty() {
+            lines.push(line.trim_end().to_string());
+            line.clear();
+            if token.trim().is_empty() {
+                continue;
+            }
+        }
+        if width(&line) + width(&token) > max_width {
+            // A word longer than a line
+            for c in token.chars() {
+                if width(&line) + char_width(c) > max_width && !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                line.push(c);
+            }
+        } else {
+            line.push_str(&token);
+        }
+    }
+    if !line.trim().is_empty() || lines.is_empty() {
+        lines.push(line.trim_end().to_string());
+    }
+    lines
+}
+
+fn is_wide(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x11FF
+        | 0x2E80..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x20000..=0x3FFFD)
+}
+
+fn is_closing(c: char) -> bool {
+    "，。、；：？！）》」』】〉”’…,.;:?!)]}".contains(c)
+}
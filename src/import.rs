@@ -0,0 +1,38 @@
+//! Turning content from outside the vault into a `PlainText`, to be saved as
+//! a new .safe file. SafeNotes has its own module, `safe_note`.
+
+use std::io::Cursor;
+
+use crate::data_structures::{image_digest, image_placeholder, PlainText};
+
+mod day_one;
+mod enex;
+mod markdown;
+
+pub use day_one::import_day_one;
+pub use enex::import_enex;
+pub use markdown::import_markdown;
+
+/// Add an image to the file unless it is already there, converting it to
+/// PNG if needed, and return the placeholder line showing it. None if the
+/// data is not an image.
+fn embed_image(plaintext: &mut PlainText, data: Vec<u8>) -> Option<String> {
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    let png = if data.starts_with(PNG_SIGNATURE) {
+        data
+    } else {
+        let image = image::load_from_memory(&data).ok()?;
+        let mut png = Cursor::new(vec![]);
+        image.write_to(&mut png, image::ImageFormat::Png).ok()?;
+        png.into_inner()
+    };
+    let digest = image_digest(&png);
+    if !plaintext
+        .images()
+        .iter()
+        .any(|image| image_digest(image) == digest)
+    {
+        plaintext.images_mut().push(png);
+    }
+    Some(image_placeholder(&digest))
+}
@@ -0,0 +1,211 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::Read,
+    path::Path,
+};
+
+use regex::Regex;
+use serde::Deserialize;
+use zip::ZipArchive;
+
+use super::embed_image;
+use crate::{
+    data_structures::{
+        Passage, PlainText, METADATA_CREATED, METADATA_MODIFIED, METADATA_SOURCE_ID, METADATA_TAGS,
+    },
+    error::Error,
+};
+
+/// Make a passage of every entry in a Day One JSON export, the zip with
+/// `Journal.json` and a `photos` folder, oldest first. The passages are
+/// titled by the dates of the entries, and the photos they show are embedded
+/// and shown by placeholders. The dates and the tags of the entries are kept
+/// as the metadata of the passages.
+pub fn import_day_one(path: &Path) -> Result<PlainText, Error> {
+    let map_err = |err: zip::result::ZipError| {
+        Error::FailedToOpenFile(format!("Failed to read {}: {:?}", path.display(), err))
+    };
+    let file = File::open(path).map_err(|err| {
+        Error::FailedToOpenFile(format!("Failed to open {}: {:?}", path.display(), err))
+    })?;
+    let mut zip = ZipArchive::new(file).map_err(map_err)?;
+
+    // An export of several journals has a JSON file for each of them
+    let mut journals = vec![];
+    // Photos are named by their MD5
+    let mut photos = HashMap::new();
+    for i in 0..zip.len() {
+        let name = zip.by_index(i).map_err(map_err)?.name().to_string();
+        if !name.contains('/') && name.ends_with(".json") {
+            journals.push(name);
+        } else if let Some(photo) = name.strip_prefix("photos/") {
+            let md5 = photo.split('.').next().unwrap_or(photo).to_string();
+            photos.insert(md5, name);
+        }
+    }
+    journals.sort();
+
+    let mut entries = vec![];
+    let mut found_journal = false;
+    for name in journals {
+        let json = read_entry(&mut zip, &name).map_err(map_err)?;
+        let json: serde_json::Value = serde_json::from_slice(&json)
+            .map_err(|err| Error::FailedToParseJson(format!("{}: {:?}", name, err)))?;
+        // Other JSON files next to the journals, e.g., added by hand, are
+        // not a reason to give up the import
+        if json.get("entries").is_none() {
+            continue;
+        }
+        let journal: Journal = serde_json::from_value(json)
+            .map_err(|err| Error::FailedToParseJson(format!("{}: {:?}", name, err)))?;
+        entries.extend(journal.entries);
+        found_journal = true;
+    }
+    if !found_journal {
+        return Err(Error::FailedToOpenFile(format!(
+            "No Journal.json in {}",
+            path.display()
+        )));
+    }
+    // By the time they stand for, as the offsets of the dates may differ.
+    // Dates that cannot be read go last.
+    entries.sort_by_cached_key(|entry| {
+        let created = parse_date(&entry.creation_date);
+        (created.is_none(), created, entry.creation_date.clone())
+    });
+
+    let moment = Regex::new(r"!\[[^\]]*\]\(dayone-moment:/+([^)\s]+)\)").unwrap();
+    let mut plaintext = PlainText::empty();
+    let mut passages = vec![];
+    for entry in entries {
+        let mut images = BTreeMap::new();
+        for photo in &entry.photos {
+            let (Some(identifier), Some(md5)) = (&photo.identifier, &photo.md5) else {
+                continue;
+            };
+            let Some(name) = photos.get(md5) else {
+                continue;
+            };
+            let data = read_entry(&mut zip, name).map_err(map_err)?;
+            if let Some(placeholder) = embed_image(&mut plaintext, data) {
+                images.insert(identifier.clone(), placeholder);
+            }
+        }
+
+        let mut lines = vec![];
+        for line in unescape(&entry.text).split('\n') {
+            let mut rest_start = 0;
+            for link in moment.captures_iter(line) {
+                let Some(placeholder) = images.get(link.get(1).unwrap().as_str()) else {
+                    continue;
+                };
+                let whole = link.get(0).unwrap();
+                let before = line[rest_start..whole.start()].trim();
+                if !before.is_empty() {
+                    lines.push(before.to_string());
+                }
+                lines.push(placeholder.clone());
+                rest_start = whole.end();
+            }
+            let after = &line[rest_start..];
+            if rest_start == 0 {
+                lines.push(after.to_string());
+            } else if !after.trim().is_empty() {
+                lines.push(after.trim().to_string());
+            }
+        }
+        // Photos the text does not show are kept at the end
+        for placeholder in images.values() {
+            if !lines.contains(placeholder) {
+                lines.push(placeholder.clone());
+            }
+        }
+        let content = lines.join("\n").trim_matches('\n').to_string();
+
+        let created = parse_date(&entry.creation_date);
+        let title = created
+            .map(|date| local_date(date, entry.time_zone.as_deref()))
+            .unwrap_or_else(|| entry.creation_date.clone());
+        let mut metadata = BTreeMap::new();
+        if let Some(created) = created {
+            metadata.insert(METADATA_CREATED.to_string(), created.to_rfc3339());
+        }
+        if let Some(modified) = entry.modified_date.as_deref().and_then(parse_date) {
+            metadata.insert(METADATA_MODIFIED.to_string(), modified.to_rfc3339());
+        }
+        if !entry.tags.is_empty() {
+            metadata.insert(METADATA_TAGS.to_string(), entry.tags.join(", "));
+        }
+        if let Some(uuid) = entry.uuid {
+            metadata.insert(METADATA_SOURCE_ID.to_string(), uuid);
+        }
+        passages.push(Passage::new(0, title, content).with_metadata(metadata));
+    }
+    let images = std::mem::take(plaintext.images_mut());
+    Ok(PlainText::from_passages_images(passages, images))
+}
+
+#[derive(Deserialize)]
+struct Journal {
+    entries: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    uuid: Option<String>,
+    creation_date: String,
+    modified_date: Option<String>,
+    /// Where the entry was written, e.g., `Asia/Shanghai`
+    time_zone: Option<String>,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    photos: Vec<Photo>,
+}
+
+#[derive(Deserialize)]
+struct Photo {
+    identifier: Option<String>,
+    md5: Option<String>,
+}
+
+fn read_entry(zip: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>, zip::result::ZipError> {
+    let mut file = zip.by_name(name)?;
+    let mut data = vec![];
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn parse_date(date: &str) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    chrono::DateTime::parse_from_rfc3339(date).ok()
+}
+
+/// The day of `time` where the entry was written, or where the import runs
+/// if that is not known.
+fn local_date(time: chrono::DateTime<chrono::FixedOffset>, time_zone: Option<&str>) -> String {
+    match time_zone.and_then(|time_zone| time_zone.parse::<chrono_tz::Tz>().ok()) {
+        Some(time_zone) => time.with_timezone(&time_zone).format("%Y-%m-%d"),
+        None => time.with_timezone(&chrono::Local).format("%Y-%m-%d"),
+    }
+    .to_string()
+}
+
+/// Day One escapes the Markdown punctuation in the text, as in `1\. item`.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(next) if c == '\\' && next.is_ascii_punctuation() => {
+                unescaped.push(*next);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
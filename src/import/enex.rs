@@ -0,0 +1,306 @@
+use std::{borrow::Cow, collections::BTreeMap, path::Path};
+
+use md5::{Digest, Md5};
+use quick_xml::{
+    escape::resolve_html5_entity,
+    events::{BytesStart, BytesText, Event},
+    Reader,
+};
+
+use super::embed_image;
+use crate::{
+    data_structures::{Passage, PlainText, METADATA_CREATED, METADATA_MODIFIED, METADATA_TAGS},
+    encode::base64_decode_to_bytes,
+    error::Error,
+};
+
+/// Make a passage of every note in an Evernote export, in the order they
+/// are in it. The content is turned into Markdown, and the images it shows
+/// are embedded and shown by placeholders. The dates and the tags of the
+/// notes are kept as the metadata of the passages.
+pub fn import_enex(path: &Path) -> Result<PlainText, Error> {
+    let xml = std::fs::read_to_string(path).map_err(|err| {
+        Error::FailedToOpenFile(format!("Failed to read {}: {:?}", path.display(), err))
+    })?;
+
+    let mut plaintext = PlainText::empty();
+    let mut passages = vec![];
+    for note in parse_notes(&xml)? {
+        // The notes refer to their resources by the MD5 of the data
+        let mut images = BTreeMap::new();
+        for resource in &note.resources {
+            if !resource.mime.starts_with("image/") {
+                continue;
+            }
+            let Ok(data) = base64_decode_to_bytes(&resource.data) else {
+                continue;
+            };
+            let hash = format!("{:x}", Md5::digest(&data));
+            if let Some(placeholder) = embed_image(&mut plaintext, data) {
+                images.insert(hash, placeholder);
+            }
+        }
+
+        let mut metadata = BTreeMap::new();
+        for (key, date) in [
+            (METADATA_CREATED, &note.created),
+            (METADATA_MODIFIED, &note.updated),
+        ] {
+            if let Some(date) = parse_date(date) {
+                metadata.insert(key.to_string(), date);
+            }
+        }
+        if !note.tags.is_empty() {
+            metadata.insert(METADATA_TAGS.to_string(), note.tags.join(", "));
+        }
+        let mut content = enml_to_markdown(&note.content, &images)?;
+        // Images the content does not show are kept at the end
+        for placeholder in images.values() {
+            if !content.split('\n').any(|line| line == placeholder) {
+                if !content.is_empty() {
+                    content.push('\n');
+                }
+                content.push_str(placeholder);
+            }
+        }
+        passages.push(Passage::new(0, note.title, content).with_metadata(metadata));
+    }
+    let images = std::mem::take(plaintext.images_mut());
+    Ok(PlainText::from_passages_images(passages, images))
+}
+
+#[derive(Default)]
+struct Note {
+    title: String,
+    content: String,
+    created: String,
+    updated: String,
+    tags: Vec<String>,
+    resources: Vec<Resource>,
+}
+
+#[derive(Default)]
+struct Resource {
+    /// Base64, with line breaks
+    data: String,
+    mime: String,
+}
+
+fn xml_error(err: impl std::fmt::Debug) -> Error {
+    Error::FailedToParseXml(format!("{:?}", err))
+}
+
+/// Text with the HTML entities ENML may use resolved, and the unknown ones
+/// dropped.
+fn unescape<'a>(text: &BytesText<'a>) -> Result<Cow<'a, str>, Error> {
+    text.unescape_with(|entity| resolve_html5_entity(entity).or(Some("")))
+        .map_err(xml_error)
+}
+
+fn parse_notes(xml: &str) -> Result<Vec<Note>, Error> {
+    let mut reader = Reader::from_str(xml);
+    let mut notes = vec![];
+    let mut note: Option<Note> = None;
+    let mut resource: Option<Resource> = None;
+    // The element whose text is being read
+    let mut element = Vec::new();
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(start) => {
+                match start.local_name().as_ref() {
+                    b"note" => note = Some(Note::default()),
+                    b"resource" => resource = Some(Resource::default()),
+                    _ => {}
+                }
+                element = start.local_name().as_ref().to_vec();
+            }
+            Event::End(end) => {
+                match (end.local_name().as_ref(), &mut note) {
+                    (b"note", Some(_)) => notes.extend(note.take()),
+                    (b"resource", Some(note)) => note.resources.extend(resource.take()),
+                    _ => {}
+                }
+                element.clear();
+            }
+            Event::Text(text) => {
+                let text = unescape(&text)?;
+                append_text(&mut note, &mut resource, &element, &text);
+            }
+            Event::CData(data) => {
+                let text = data.decode().map_err(xml_error)?;
+                append_text(&mut note, &mut resource, &element, &text);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if notes.is_empty() && !xml.contains("<en-export") {
+        return Err(Error::FailedToParseXml(
+            "not an Evernote export".to_string(),
+        ));
+    }
+    Ok(notes)
+}
+
+fn append_text(
+    note: &mut Option<Note>,
+    resource: &mut Option<Resource>,
+    element: &[u8],
+    text: &str,
+) {
+    let Some(note) = note else {
+        return;
+    };
+    if let Some(resource) = resource {
+        match element {
+            b"data" => resource
+                .data
+                .extend(text.chars().filter(|c| !c.is_whitespace())),
+            b"mime" => resource.mime.push_str(text.trim()),
+            _ => {}
+        }
+        return;
+    }
+    match element {
+        b"title" => note.title.push_str(text),
+        b"content" => note.content.push_str(text),
+        b"created" => note.created.push_str(text.trim()),
+        b"updated" => note.updated.push_str(text.trim()),
+        b"tag" => note.tags.push(text.trim().to_string()),
+        _ => {}
+    }
+}
+
+/// Evernote writes dates like 20240131T235959Z.
+fn parse_date(date: &str) -> Option<String> {
+    chrono::NaiveDateTime::parse_from_str(date, "%Y%m%dT%H%M%SZ")
+        .ok()
+        .map(|date| date.and_utc().to_rfc3339())
+}
+
+/// ENML is XHTML. Blocks become lines, headings, list items and checkboxes
+/// are written as in Markdown, and the images are replaced by the
+/// placeholders in `images`, by their hashes. Other formatting is dropped.
+fn enml_to_markdown(enml: &str, images: &BTreeMap<String, String>) -> Result<String, Error> {
+    let mut reader = Reader::from_str(enml);
+    let mut text = String::new();
+    // Encrypted parts cannot be read
+    let mut in_crypt = false;
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(start) => {
+                start_element(&start, &mut text, images);
+                in_crypt |= start.local_name().as_ref() == b"en-crypt";
+            }
+            Event::Empty(start) => {
+                start_element(&start, &mut text, images);
+            }
+            Event::End(end) => match end.local_name().as_ref() {
+                b"en-crypt" => in_crypt = false,
+                name if is_block(name) => new_line(&mut text),
+                _ => {}
+            },
+            Event::Text(t) if !in_crypt => {
+                // Line breaks in the source are not line breaks in the note
+                let t = unescape(&t)?.replace(['\n', '\r'], " ");
+                let t = t.replace('\u{a0}', " ");
+                if text.is_empty() || text.ends_with('\n') {
+                    text.push_str(t.trim_start());
+                } else {
+                    text.push_str(&t);
+                }
+            }
+            Event::CData(data) if !in_crypt => {
+                text.push_str(&data.decode().map_err(xml_error)?);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    // At most one blank line in a row, and none around the text
+    let mut lines = vec![];
+    for line in text.split('\n').map(str::trim_end) {
+        if !(line.is_empty() && lines.last().is_none_or(|last: &&str| last.is_empty())) {
+            lines.push(line);
+        }
+    }
+    while lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+    Ok(lines.join("\n"))
+}
+
+fn is_block(name: &[u8]) -> bool {
+    matches!(
+        name,
+        b"div"
+            | b"p"
+            | b"li"
+            | b"tr"
+            | b"blockquote"
+            | b"pre"
+            | b"ul"
+            | b"ol"
+            | b"table"
+            | b"h1"
+            | b"h2"
+            | b"h3"
+            | b"h4"
+            | b"h5"
+            | b"h6"
+            | b"en-note"
+    )
+}
+
+fn new_line(text: &mut String) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+fn start_element(start: &BytesStart, text: &mut String, images: &BTreeMap<String, String>) {
+    let attribute = |name: &str| {
+        start
+            .try_get_attribute(name)
+            .ok()
+            .flatten()
+            .and_then(|value| value.unescape_value().ok())
+            .map(|value| value.into_owned())
+    };
+    let name = start.local_name();
+    match name.as_ref() {
+        b"br" => text.push('\n'),
+        b"hr" => {
+            new_line(text);
+            text.push_str("---\n");
+        }
+        b"h1" | b"h2" | b"h3" | b"h4" | b"h5" | b"h6" => {
+            new_line(text);
+            let level = (name.as_ref()[1] - b'0') as usize;
+            text.push_str(&format!("{} ", "#".repeat(level)));
+        }
+        b"li" => {
+            new_line(text);
+            text.push_str("- ");
+        }
+        b"en-todo" => {
+            let checked = attribute("checked").is_some_and(|checked| checked == "true");
+            text.push_str(if checked { "[x] " } else { "[ ] " });
+        }
+        b"en-media" => {
+            let placeholder = attribute("hash").and_then(|hash| images.get(&hash));
+            match placeholder {
+                Some(placeholder) => {
+                    new_line(text);
+                    text.push_str(placeholder);
+                    text.push('\n');
+                }
+                None => text.push_str("[attachment]"),
+            }
+        }
+        b"en-crypt" => text.push_str("[encrypted]"),
+        name if is_block(name) => new_line(text),
+        _ => {}
+    }
+}
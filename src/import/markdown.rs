@@ -0,0 +1,115 @@
+use std::path::Path;
+
+use regex::Regex;
+
+use super::embed_image;
+use crate::{data_structures::PlainText, error::Error};
+
+/// Make a passage of every `.md`, `.markdown` and `.txt` file directly in
+/// `dir`, in the order of the file names. The title is the `title` of the
+/// front matter, or a heading on the first line, or else the file name.
+/// Images linked by a local path are embedded and shown by placeholders; any
+/// other link is kept as it is.
+pub fn import_markdown(dir: &Path) -> Result<PlainText, Error> {
+    let map_err = |err: std::io::Error| {
+        Error::FailedToOpenFile(format!("Failed to read {}: {:?}", dir.display(), err))
+    };
+    let mut paths = std::fs::read_dir(dir)
+        .map_err(map_err)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(map_err)?;
+    paths.retain(|path| {
+        path.is_file()
+            && matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("md" | "markdown" | "txt")
+            )
+    });
+    paths.sort();
+
+    let image_link = Regex::new(r#"!\[[^\]]*\]\(\s*<?([^)>\s]+)>?(?:\s+"[^"]*")?\s*\)"#).unwrap();
+    let mut plaintext = PlainText::empty();
+    for path in paths {
+        let text = std::fs::read_to_string(&path).map_err(|err| {
+            Error::FailedToOpenFile(format!("Failed to read {}: {:?}", path.display(), err))
+        })?;
+        let text = text.replace("\r\n", "\n");
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let (title, body) = split_title(&text, stem);
+        let base = path.parent().unwrap_or(dir);
+        let content = body
+            .split('\n')
+            .map(|line| embed_images(&mut plaintext, line, base, &image_link))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let index = plaintext.num_passages();
+        plaintext.insert_new_passage(index, title)?;
+        plaintext.set_content(index, content.trim_matches('\n').to_string())?;
+    }
+    Ok(plaintext)
+}
+
+/// The title and the rest of the text, without the front matter or the
+/// heading the title is taken from.
+fn split_title(text: &str, file_stem: String) -> (String, &str) {
+    let mut body = text;
+    let mut title = None;
+    if let Some(rest) = text.strip_prefix("---\n") {
+        if let Some(end) = rest.find("\n---") {
+            let front_matter = &rest[..end];
+            body = rest[end + 4..].trim_start_matches(|c| c != '\n');
+            title = front_matter.lines().find_map(|line| {
+                let value = line.strip_prefix("title:")?.trim();
+                Some(value.trim_matches(|c| c == '"' || c == '\'').to_string())
+            });
+        }
+    }
+    let body = body.trim_start_matches('\n');
+    if title.is_none() {
+        let first_line = body.split('\n').next().unwrap_or("");
+        let heading = first_line.trim_start_matches('#');
+        if heading.len() < first_line.len() && heading.starts_with(' ') {
+            let rest = &body[first_line.len()..];
+            return (heading.trim().to_string(), rest);
+        }
+    }
+    (title.unwrap_or(file_stem), body)
+}
+
+/// Replace the local image links in a line with placeholders, each on a line
+/// of its own as the editor needs.
+fn embed_images(plaintext: &mut PlainText, line: &str, base: &Path, image_link: &Regex) -> String {
+    let mut lines = vec![];
+    let mut rest_start = 0;
+    for link in image_link.captures_iter(line) {
+        let target = link.get(1).unwrap().as_str();
+        if target.contains("://") || target.starts_with("data:") {
+            continue;
+        }
+        let Some(placeholder) = std::fs::read(base.join(target.replace("%20", " ")))
+            .ok()
+            .and_then(|data| embed_image(plaintext, data))
+        else {
+            continue;
+        };
+        let whole = link.get(0).unwrap();
+        let before = line[rest_start..whole.start()].trim();
+        if !before.is_empty() {
+            lines.push(before.to_string());
+        }
+        lines.push(placeholder);
+        rest_start = whole.end();
+    }
+    if rest_start == 0 {
+        return line.to_string();
+    }
+    let after = line[rest_start..].trim();
+    if !after.is_empty() {
+        lines.push(after.to_string());
+    }
+    lines.join("\n")
+}
@@ -1,8 +1,19 @@
+#[cfg(unix)]
+pub mod agent;
 pub mod app;
+pub mod backup;
 pub mod cipher;
+pub mod config;
 pub mod consts;
 pub mod data_structures;
 pub mod encode;
 pub mod error;
+pub mod export;
+pub mod import;
+pub mod merge;
+pub mod password;
 pub mod png;
+pub mod rekey;
 pub mod safe_note;
+pub mod storage;
+pub mod verify;
@@ -0,0 +1,130 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    data_structures::{image_digest, image_reference, Passage, PlainText},
+    error::Error,
+};
+
+/// How alike two passages must be to be reported as near-identical, see
+/// `similarity`.
+pub const SIMILARITY_THRESHOLD: f64 = 0.9;
+
+/// What a passage of a file being merged in is to the passages already in
+/// the file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PassageMatch {
+    New,
+    /// Same title and content as the passage at this index, ignoring
+    /// trailing whitespace
+    Identical(usize),
+    /// The most alike passage, at least `SIMILARITY_THRESHOLD` alike
+    Similar {
+        index: usize,
+        similarity: f64,
+    },
+}
+
+/// Compare every passage of `incoming` with the passages of `existing`.
+pub fn match_passages(existing: &PlainText, incoming: &PlainText) -> Vec<PassageMatch> {
+    let existing = existing
+        .passages()
+        .iter()
+        .map(|passage| {
+            let text = normalize(passage);
+            let bigrams = bigrams(&text);
+            (text, bigrams)
+        })
+        .collect::<Vec<_>>();
+    incoming
+        .passages()
+        .iter()
+        .map(|passage| {
+            let text = normalize(passage);
+            if let Some(index) = existing.iter().position(|(other, _)| *other == text) {
+                return PassageMatch::Identical(index);
+            }
+            let bigrams = bigrams(&text);
+            existing
+                .iter()
+                .enumerate()
+                .map(|(index, (_, other))| (index, similarity(&bigrams, other)))
+                .filter(|(_, similarity)| *similarity >= SIMILARITY_THRESHOLD)
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .map_or(PassageMatch::New, |(index, similarity)| {
+                    PassageMatch::Similar { index, similarity }
+                })
+        })
+        .collect()
+}
+
+/// The title and the content, without the whitespace editors tend to add or
+/// remove at the ends of lines.
+fn normalize(passage: &Passage) -> String {
+    let content = passage
+        .content()
+        .trim_end()
+        .split('\n')
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("{}\n{}", passage.title().trim(), content)
+}
+
+/// Pairs of adjacent characters, counted. Characters rather than words, so
+/// that CJK text without spaces is compared as well.
+fn bigrams(text: &str) -> HashMap<(char, char), usize> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut bigrams = HashMap::new();
+    for pair in chars.windows(2) {
+        *bigrams.entry((pair[0], pair[1])).or_insert(0) += 1;
+    }
+    bigrams
+}
+
+/// The Sørensen–Dice coefficient of the bigrams: 1 when they are the same,
+/// 0 when none is shared.
+fn similarity(a: &HashMap<(char, char), usize>, b: &HashMap<(char, char), usize>) -> f64 {
+    let total = a.values().sum::<usize>() + b.values().sum::<usize>();
+    if total == 0 {
+        return 1.0;
+    }
+    // At most the smaller over the mean, so lengths far apart need no
+    // closer look
+    let smaller = a.values().sum::<usize>().min(b.values().sum::<usize>());
+    if 2.0 * (smaller as f64) / (total as f64) < SIMILARITY_THRESHOLD {
+        return 0.0;
+    }
+    let common = a
+        .iter()
+        .map(|(bigram, n)| (*n).min(b.get(bigram).copied().unwrap_or(0)))
+        .sum::<usize>();
+    2.0 * common as f64 / total as f64
+}
+
+/// The images of `plaintext` that the passages at `indices` show, each once,
+/// in the order they are first shown.
+pub fn images_of_passages<'a>(
+    plaintext: &'a PlainText,
+    indices: &[usize],
+) -> Result<Vec<&'a Vec<u8>>, Error> {
+    let images = plaintext
+        .images()
+        .iter()
+        .map(|image| (image_digest(image), image))
+        .collect::<HashMap<_, _>>();
+    let mut seen = HashSet::new();
+    let mut shown = vec![];
+    for &index in indices {
+        for line in plaintext.passage(index)?.content().split('\n') {
+            let Some(digest) = image_reference(line) else {
+                continue;
+            };
+            if let Some(image) = images.get(digest) {
+                if seen.insert(digest) {
+                    shown.push(*image);
+                }
+            }
+        }
+    }
+    Ok(shown)
+}
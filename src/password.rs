@@ -0,0 +1,116 @@
+use std::io::BufRead;
+
+use crate::error::Error;
+
+/// Where to get a password from without asking on a terminal, for scripts
+/// and cron jobs. The password is read once and can then be passed to any
+/// function taking `password: &str`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordSource {
+    /// The first line read from an open file descriptor
+    Fd(i32),
+    /// The value of an environment variable
+    Env(String),
+    /// The first line printed by a shell command, e.g., `pass show vault`
+    Command(String),
+    /// The first line of stdin. The rest of stdin is left for the caller.
+    Stdin,
+}
+
+impl PasswordSource {
+    pub fn read(&self) -> Result<String, Error> {
+        let password = match self {
+            PasswordSource::Fd(fd) => read_fd(*fd)?,
+            PasswordSource::Env(name) => std::env::var(name).map_err(|err| {
+                Error::FailedToReadPassword(format!(
+                    "Failed to read environment variable {}: {:?}",
+                    name, err
+                ))
+            })?,
+            PasswordSource::Command(command) => run_command(command)?,
+            PasswordSource::Stdin => {
+                let mut line = String::new();
+                std::io::stdin()
+                    .lock()
+                    .read_line(&mut line)
+                    .map_err(|err| Error::FailedToReadPassword(format!("{:?}", err)))?;
+                line
+            }
+        };
+        let password = first_line(&password);
+        if password.is_empty() {
+            return Err(Error::FailedToReadPassword("Empty password".to_string()));
+        }
+        Ok(password.to_string())
+    }
+}
+
+fn first_line(text: &str) -> &str {
+    let line = text.split('\n').next().unwrap_or("");
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+#[cfg(unix)]
+fn read_fd(fd: i32) -> Result<String, Error> {
+    use std::{io::Read, os::fd::FromRawFd};
+
+    // A negative descriptor is never open, and `from_raw_fd` must not be
+    // given one
+    if fd < 0 {
+        return Err(Error::FailedToReadPassword(format!(
+            "Invalid file descriptor {}",
+            fd
+        )));
+    }
+    // The descriptor belongs to whoever passed it, so it is not closed here.
+    // Read byte by byte to stop right after the first line.
+    let file = std::mem::ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) });
+    let mut line = vec![];
+    let mut byte = [0u8];
+    loop {
+        match (&*file).read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) => line.push(byte[0]),
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                return Err(Error::FailedToReadPassword(format!(
+                    "Failed to read fd {}: {:?}",
+                    fd, err
+                )))
+            }
+        }
+    }
+    String::from_utf8(line).map_err(|_| Error::InvalidUTF8)
+}
+
+#[cfg(not(unix))]
+fn read_fd(fd: i32) -> Result<String, Error> {
+    Err(Error::FailedToReadPassword(format!(
+        "Reading from fd {} is only supported on Unix",
+        fd
+    )))
+}
+
+fn run_command(command: &str) -> Result<String, Error> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let output = std::process::Command::new(shell)
+        .args([flag, command])
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .map_err(|err| {
+            Error::FailedToReadPassword(format!("Failed to run {}: {:?}", command, err))
+        })?;
+    if !output.status.success() {
+        return Err(Error::FailedToReadPassword(format!(
+            "{} exited with {}",
+            command, output.status
+        )));
+    }
+    String::from_utf8(output.stdout).map_err(|_| Error::InvalidUTF8)
+}
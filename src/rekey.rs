@@ -0,0 +1,84 @@
+use std::fmt::Display;
+
+use crate::{data_structures::PlainText, error::Error, storage::Storage};
+
+#[derive(Debug)]
+pub enum RekeyOutcome {
+    /// Dry run only: the file decrypts and would be written.
+    WouldRekey,
+    Rekeyed,
+    /// Created but never saved, so there is no password to change.
+    SkippedEmpty,
+    Failed(Error),
+}
+
+impl RekeyOutcome {
+    pub fn is_failed(&self) -> bool {
+        matches!(self, RekeyOutcome::Failed(_))
+    }
+}
+
+impl Display for RekeyOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RekeyOutcome::WouldRekey => write!(f, "would be re-encrypted"),
+            RekeyOutcome::Rekeyed => write!(f, "re-encrypted"),
+            RekeyOutcome::SkippedEmpty => write!(f, "skipped, never saved"),
+            RekeyOutcome::Failed(err) => write!(f, "failed: {:?}", err),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RekeyReport {
+    pub file: String,
+    pub outcome: RekeyOutcome,
+}
+
+/// Decrypt every file and encrypt it again in the latest format, with the new
+/// password if one is given. With `dry_run`, nothing is written, but
+/// everything else is done, so the report tells which files would fail.
+pub fn rekey_files(
+    storage: &dyn Storage,
+    files: &[String],
+    password: &str,
+    new_password: Option<&str>,
+    dry_run: bool,
+) -> Vec<RekeyReport> {
+    files
+        .iter()
+        .map(|file| RekeyReport {
+            file: file.clone(),
+            outcome: match rekey_file(storage, file, password, new_password, dry_run) {
+                Ok(outcome) => outcome,
+                Err(err) => RekeyOutcome::Failed(err),
+            },
+        })
+        .collect()
+}
+
+fn rekey_file(
+    storage: &dyn Storage,
+    file: &str,
+    password: &str,
+    new_password: Option<&str>,
+    dry_run: bool,
+) -> Result<RekeyOutcome, Error> {
+    let ciphertext = String::from_utf8(storage.read(file)?).map_err(|_| Error::InvalidUTF8)?;
+    if ciphertext.is_empty() {
+        return Ok(RekeyOutcome::SkippedEmpty);
+    }
+    let plaintext = PlainText::decrypt(password, &ciphertext)?;
+    let new_password = new_password.unwrap_or(password);
+    let new_ciphertext = plaintext.encrypt(new_password);
+    // Make sure the new file can be read back before replacing the old one
+    let decrypted = PlainText::decrypt(new_password, &new_ciphertext)?;
+    if decrypted.encode() != plaintext.encode() {
+        return Err(Error::DecryptionFail);
+    }
+    if dry_run {
+        return Ok(RekeyOutcome::WouldRekey);
+    }
+    storage.write(file, new_ciphertext.as_bytes())?;
+    Ok(RekeyOutcome::Rekeyed)
+}
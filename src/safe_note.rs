@@ -1,12 +1,18 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sha2::Digest;
+use std::collections::BTreeMap;
 
 use crate::{
-    data_structures::{Passage, PlainText},
-    encode::base64_decode_to_bytes,
+    data_structures::{
+        Passage, PlainText, METADATA_CREATED, METADATA_MODIFIED, METADATA_SOURCE_FIELDS,
+        METADATA_SOURCE_ID, METADATA_TAGS,
+    },
+    encode::{base64_decode_to_bytes, base64_encode},
     error::Error,
 };
 
@@ -16,6 +22,19 @@ pub struct SafeNoteFile {
 }
 
 impl SafeNoteFile {
+    pub fn from_passages<'a>(passages: impl IntoIterator<Item = &'a Passage>) -> Self {
+        Self {
+            records: passages
+                .into_iter()
+                .map(SafeNoteRecord::from_passage)
+                .collect(),
+        }
+    }
+
+    pub fn records(&self) -> &[SafeNoteRecord] {
+        &self.records
+    }
+
     pub fn into_passages(&self) -> Vec<Passage> {
         self.records.iter().map(|p| p.into_passage()).collect()
     }
@@ -25,35 +44,328 @@ impl SafeNoteFile {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// A note as SafeNotes exports it. Only the title and the description are
+/// encrypted. The names of the other fields differ between versions of
+/// SafeNotes, so the usual ones are all accepted, and the rest are kept as
+/// they are.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct SafeNoteRecord {
     title: String,
     description: String,
+    #[serde(default, alias = "_id", skip_serializing_if = "Option::is_none")]
+    id: Option<Value>,
+    #[serde(
+        default,
+        rename = "createdTime",
+        alias = "created_time",
+        alias = "createdAt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    created_time: Option<Value>,
+    #[serde(
+        default,
+        rename = "editedTime",
+        alias = "edited_time",
+        alias = "lastUpdated",
+        alias = "updatedTime",
+        alias = "updatedAt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    edited_time: Option<Value>,
+    #[serde(
+        default,
+        alias = "categories",
+        alias = "tags",
+        skip_serializing_if = "Option::is_none"
+    )]
+    category: Option<Value>,
+    #[serde(flatten)]
+    extra: BTreeMap<String, Value>,
+    /// The fields other than the title and the description as they are in
+    /// the export, with their own names and types
+    #[serde(skip)]
+    source: BTreeMap<String, Value>,
 }
 
 impl SafeNoteRecord {
+    /// The metadata of the passage goes back into the fields it is read
+    /// from. Fields that still read as the metadata they gave are written
+    /// as they were imported, keeping their names and types.
+    pub fn from_passage(passage: &Passage) -> Self {
+        let mut metadata = passage.metadata().clone();
+        let source = metadata
+            .remove(METADATA_SOURCE_FIELDS)
+            .and_then(|fields| serde_json::from_str::<BTreeMap<String, Value>>(&fields).ok())
+            .unwrap_or_default();
+        let mut extra = BTreeMap::new();
+        for (field, value) in source {
+            let (key, read) = field_to_metadata(&field, &value);
+            match read {
+                // Not read into the metadata, e.g., null
+                None if !metadata.contains_key(&key) => {
+                    extra.insert(field, value);
+                }
+                Some(read) if metadata.get(&key) == Some(&read) => {
+                    metadata.remove(&key);
+                    extra.insert(field, value);
+                }
+                // Changed since the import
+                _ => {}
+            }
+        }
+        let mut take = |key: &str| metadata.remove(key).map(Value::String);
+        let id = take(METADATA_SOURCE_ID);
+        let created_time = take(METADATA_CREATED);
+        let edited_time = take(METADATA_MODIFIED);
+        let category = take(METADATA_TAGS);
+        extra.extend(
+            metadata
+                .into_iter()
+                .map(|(key, value)| (key, Value::String(value))),
+        );
+        Self {
+            title: passage.title().clone(),
+            description: passage.content().clone(),
+            id,
+            created_time,
+            edited_time,
+            category,
+            extra,
+            source: BTreeMap::new(),
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// The fields other than the title and the description become the
+    /// metadata of the passage.
     pub fn into_passage(&self) -> Passage {
-        Passage::new(0, self.title.clone(), self.description.clone())
+        let mut metadata = BTreeMap::new();
+        let fields = [
+            (
+                METADATA_SOURCE_ID,
+                self.id.as_ref().and_then(value_to_string),
+            ),
+            (
+                METADATA_CREATED,
+                self.created_time.as_ref().and_then(time_to_string),
+            ),
+            (
+                METADATA_MODIFIED,
+                self.edited_time.as_ref().and_then(time_to_string),
+            ),
+            (
+                METADATA_TAGS,
+                self.category.as_ref().and_then(tags_to_string),
+            ),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                metadata.insert(key.to_string(), value);
+            }
+        }
+        for (key, value) in &self.extra {
+            if let Some(value) = value_to_string(value) {
+                metadata.insert(key.clone(), value);
+            }
+        }
+        if !self.source.is_empty() {
+            metadata.insert(
+                METADATA_SOURCE_FIELDS.to_string(),
+                serde_json::to_string(&self.source).unwrap(),
+            );
+        }
+        Passage::new(0, self.title.clone(), self.description.clone()).with_metadata(metadata)
+    }
+}
+
+/// The metadata key a field of a record is read into, and what it reads as.
+/// Matches the names accepted by `SafeNoteRecord`.
+fn field_to_metadata(field: &str, value: &Value) -> (String, Option<String>) {
+    let (key, read) = match field {
+        "id" | "_id" => (METADATA_SOURCE_ID, value_to_string(value)),
+        "createdTime" | "created_time" | "createdAt" => (METADATA_CREATED, time_to_string(value)),
+        "editedTime" | "edited_time" | "lastUpdated" | "updatedTime" | "updatedAt" => {
+            (METADATA_MODIFIED, time_to_string(value))
+        }
+        "category" | "categories" | "tags" => (METADATA_TAGS, tags_to_string(value)),
+        field => (field, value_to_string(value)),
+    };
+    (key.to_string(), read)
+}
+
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+/// Numbers are taken as milliseconds since the epoch, or seconds if too
+/// small for that.
+fn time_to_string(value: &Value) -> Option<String> {
+    let Some(number) = value.as_i64() else {
+        return value_to_string(value);
+    };
+    let millis = if number.unsigned_abs() < 100_000_000_000 {
+        number * 1000
+    } else {
+        number
+    };
+    chrono::DateTime::from_timestamp_millis(millis).map(|time| time.to_rfc3339())
+}
+
+fn tags_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::Array(tags) => {
+            let tags = tags.iter().filter_map(value_to_string).collect::<Vec<_>>();
+            (!tags.is_empty()).then(|| tags.join(", "))
+        }
+        value => value_to_string(value),
+    }
+}
+
+/// A record that could not be imported, with its position in the export.
+#[derive(Debug)]
+pub struct SafeNoteRecordError {
+    pub index: usize,
+    pub error: Error,
+}
+
+impl std::fmt::Display for SafeNoteRecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "record {}: {:?}", self.index + 1, self.error)
     }
 }
 
-pub fn load_safe_note_file(password: &str, file_path: &PathBuf) -> Result<SafeNoteFile, Error> {
+/// The records that are imported, and why the others are not.
+#[derive(Debug)]
+pub struct SafeNoteImport {
+    pub file: SafeNoteFile,
+    pub errors: Vec<SafeNoteRecordError>,
+}
+
+pub fn load_safe_note_file(password: &str, file_path: &PathBuf) -> Result<SafeNoteImport, Error> {
     let contents = std::fs::read_to_string(file_path)
         .map_err(|err| Error::FailedToOpenFile(format!("{:?}", err)))?;
-    let mut safenote: SafeNoteFile = serde_json::from_str(&contents)
+    parse_safe_note_file(password, &contents)
+}
+
+/// Decrypt the records one by one, skipping the ones that fail. It is an
+/// error only if the file is not a SafeNotes export, or no record can be
+/// decrypted, which is most likely a wrong password.
+pub fn parse_safe_note_file(password: &str, contents: &str) -> Result<SafeNoteImport, Error> {
+    let json: Value = serde_json::from_str(contents)
         .map_err(|err| Error::FailedToParseJson(format!("{:?}", err)))?;
-    for record in safenote.records.iter_mut() {
-        record.title = decrypt_safe_notes_ciphertext(password, &record.title)?;
-        record.description = decrypt_safe_notes_ciphertext(password, &record.description)?;
+    let Some(records) = json.get("records").and_then(|records| records.as_array()) else {
+        return Err(Error::InvalidSafeNotes(
+            "no \"records\" list in the file".to_string(),
+        ));
+    };
+
+    let mut imported = vec![];
+    let mut errors = vec![];
+    for (index, raw) in records.iter().enumerate() {
+        let record = serde_json::from_value::<SafeNoteRecord>(raw.clone())
+            .map_err(|err| Error::FailedToParseJson(format!("{}", err)))
+            .and_then(|mut record| {
+                record.source = source_fields(raw.clone());
+                record.title = decrypt_safe_notes_ciphertext(password, &record.title)?;
+                record.description = decrypt_safe_notes_ciphertext(password, &record.description)?;
+                Ok(record)
+            });
+        match record {
+            Ok(record) => imported.push(record),
+            Err(error) => errors.push(SafeNoteRecordError { index, error }),
+        }
+    }
+    if imported.is_empty() && !errors.is_empty() {
+        return Err(errors.remove(0).error);
+    }
+    Ok(SafeNoteImport {
+        file: SafeNoteFile { records: imported },
+        errors,
+    })
+}
+
+/// The fields of a record other than the encrypted ones.
+fn source_fields(record: Value) -> BTreeMap<String, Value> {
+    let Value::Object(fields) = record else {
+        return BTreeMap::new();
+    };
+    fields
+        .into_iter()
+        .filter(|(field, _)| field != "title" && field != "description")
+        .collect()
+}
+
+/// What OpenSSL starts a salted ciphertext with, before the 8 bytes of salt.
+const SALTED_MAGIC: &[u8] = b"Salted__";
+
+/// Encrypt every record and write them as a SafeNotes export.
+pub fn save_safe_note_file(
+    password: &str,
+    safe_note: &SafeNoteFile,
+    file_path: &Path,
+) -> Result<(), Error> {
+    std::fs::write(file_path, serialize_safe_note_file(password, safe_note))
+        .map_err(|err| Error::FailedToWriteFile(format!("{:?}", err)))
+}
+
+pub fn serialize_safe_note_file(password: &str, safe_note: &SafeNoteFile) -> String {
+    let mut safe_note = safe_note.clone();
+    for record in safe_note.records.iter_mut() {
+        record.title = encrypt_safe_notes_plaintext(password, &record.title);
+        record.description = encrypt_safe_notes_plaintext(password, &record.description);
     }
-    Ok(safenote)
+    serde_json::to_string(&safe_note).unwrap()
 }
 
+/// Encrypt as `openssl enc -aes-256-cbc -md sha256` does, with a fresh salt
+/// every time.
+pub fn encrypt_safe_notes_plaintext(password: &str, plaintext: &str) -> String {
+    let mut salt = [0u8; 8];
+    StdRng::from_os_rng().fill_bytes(&mut salt);
+    let (key, iv) = derive_key_iv(password.as_bytes(), &salt);
+    let encrypted = cbc::Encryptor::<aes::Aes256>::new(key.as_slice().into(), iv.as_slice().into())
+        .encrypt_padded_vec_mut::<Pkcs7>(plaintext.as_bytes());
+    base64_encode([SALTED_MAGIC, &salt, &encrypted].concat())
+}
+
+/// Decrypt what `openssl enc -aes-256-cbc -md sha256` gives. SafeNotes
+/// leaves empty fields unencrypted.
 pub fn decrypt_safe_notes_ciphertext(password: &str, ciphertext: &str) -> Result<String, Error> {
-    let data = base64_decode_to_bytes(ciphertext)?;
-    let salt = data[8..16].to_vec();
-    let data = data[16..].to_vec();
-    let password = password.as_bytes();
+    if ciphertext.is_empty() {
+        return Ok(String::new());
+    }
+    let data = base64_decode_to_bytes(ciphertext.trim())?;
+    let Some(data) = data.strip_prefix(SALTED_MAGIC) else {
+        return Err(Error::InvalidSafeNotes(
+            "the ciphertext has no \"Salted__\" header".to_string(),
+        ));
+    };
+    if data.len() < 8 {
+        return Err(Error::InvalidSafeNotes("the salt is cut short".to_string()));
+    }
+    let (salt, data) = data.split_at(8);
+    if data.is_empty() || data.len() % 16 != 0 {
+        return Err(Error::InvalidSafeNotes(
+            "the ciphertext is not whole AES blocks".to_string(),
+        ));
+    }
+    let (key, iv) = derive_key_iv(password.as_bytes(), salt);
+
+    cbc::Decryptor::<aes::Aes256>::new(key.as_slice().into(), iv.as_slice().into())
+        .decrypt_padded_vec_mut::<Pkcs7>(data)
+        .map_err(|_| Error::DecryptionFail)
+        .and_then(|s| String::from_utf8(s).map_err(|_| Error::InvalidUTF8))
+}
+
+/// The AES-256 key and the IV, from OpenSSL's EVP_BytesToKey with SHA256.
+fn derive_key_iv(password: &[u8], salt: &[u8]) -> (Vec<u8>, Vec<u8>) {
     let mut concatenated_hashes = Vec::<u8>::new();
     let mut current_hash = Vec::<u8>::new();
     let mut pre_hash: Vec<u8>;
@@ -62,10 +374,10 @@ pub fn decrypt_safe_notes_ciphertext(password: &str, ciphertext: &str) -> Result
         if current_hash.len() > 0 {
             pre_hash = current_hash.clone();
             pre_hash.extend_from_slice(password);
-            pre_hash.extend_from_slice(&salt);
+            pre_hash.extend_from_slice(salt);
         } else {
             pre_hash = password.to_vec();
-            pre_hash.extend_from_slice(&salt);
+            pre_hash.extend_from_slice(salt);
         }
         let mut hasher = sha2::Sha256::new();
         hasher.update(&pre_hash);
@@ -77,9 +389,5 @@ pub fn decrypt_safe_notes_ciphertext(password: &str, ciphertext: &str) -> Result
     }
     let key = concatenated_hashes[0..32].to_vec();
     let iv = concatenated_hashes[32..48].to_vec();
-
-    cbc::Decryptor::<aes::Aes256>::new(key.as_slice().into(), iv.as_slice().into())
-        .decrypt_padded_vec_mut::<Pkcs7>(&data)
-        .map_err(|_| Error::DecryptionFail)
-        .and_then(|s| String::from_utf8(s).map_err(|_| Error::InvalidUTF8))
+    (key, iv)
 }
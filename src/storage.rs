@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cipher::{key_derive, Key},
+    data_structures::PlainText,
+    error::Error,
+};
+
+mod filesystem;
+mod memory;
+mod webdav;
+
+pub use filesystem::FileSystemStorage;
+pub use memory::MemoryStorage;
+pub use webdav::WebDavStorage;
+
+/// A place where the encrypted .safe blobs are kept.
+///
+/// Blobs are addressed by the file name without the ".safe" extension, which
+/// is the name shown in the file list.
+pub trait Storage: Send + Sync {
+    fn read(&self, name: &str) -> Result<Vec<u8>, Error>;
+
+    /// Replace the whole blob, or create it. On the file system readers
+    /// never see it half written; other backends only promise what their
+    /// server does.
+    fn write(&self, name: &str, data: &[u8]) -> Result<(), Error>;
+
+    fn delete(&self, name: &str) -> Result<(), Error>;
+
+    /// Names of all the blobs, sorted.
+    fn list(&self) -> Result<Vec<String>, Error>;
+
+    fn exists(&self, name: &str) -> Result<bool, Error> {
+        Ok(self.list()?.iter().any(|n| n == name))
+    }
+
+    /// Create the empty blob of a new file. A name that is taken, or that
+    /// cannot be checked, is refused rather than risk emptying a file.
+    fn create(&self, name: &str) -> Result<(), Error> {
+        check_file_name(name)?;
+        if self.exists(name)? {
+            return Err(Error::FileExists(name.to_string()));
+        }
+        self.write(name, b"")
+    }
+
+    /// Read and decrypt a file. A file that was created but never saved is
+    /// empty and has no password yet.
+    fn load(&self, name: &str, password: &str) -> Result<PlainText, Error> {
+        self.load_with_key(name, &key_derive(password))
+    }
+
+    fn load_with_key(&self, name: &str, key: &Key) -> Result<PlainText, Error> {
+        let ciphertext = String::from_utf8(self.read(name)?).map_err(|_| Error::InvalidUTF8)?;
+        if ciphertext.is_empty() {
+            return Ok(PlainText::empty());
+        }
+        PlainText::decrypt_with_key(key, &ciphertext)
+    }
+
+    /// Encrypt and write a file. Returns the ciphertext written.
+    fn save(&self, name: &str, plaintext: &PlainText, password: &str) -> Result<String, Error> {
+        self.save_with_key(name, plaintext, &key_derive(password))
+    }
+
+    fn save_with_key(&self, name: &str, plaintext: &PlainText, key: &Key) -> Result<String, Error> {
+        let ciphertext = plaintext.encrypt_with_key(key);
+        self.write(name, ciphertext.as_bytes())?;
+        Ok(ciphertext)
+    }
+}
+
+/// Refuse names that could point outside the place the blobs are kept, e.g.,
+/// ones read from an archive.
+pub fn check_file_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+        return Err(Error::InvalidFileName(name.to_string()));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum StorageConfig {
+    /// The .safe files under the data dir
+    #[default]
+    FileSystem,
+    WebDav {
+        url: String,
+        #[serde(default)]
+        username: String,
+        #[serde(default)]
+        password: String,
+    },
+}
+
+impl StorageConfig {
+    pub fn open(&self, data_dir: &str) -> Box<dyn Storage> {
+        match self {
+            StorageConfig::FileSystem => Box::new(FileSystemStorage::new(data_dir)),
+            StorageConfig::WebDav {
+                url,
+                username,
+                password,
+            } => Box::new(WebDavStorage::new(url, username, password)),
+        }
+    }
+}
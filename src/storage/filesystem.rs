@@ -0,0 +1,79 @@
+use std::{io::Write, path::PathBuf};
+
+use super::{check_file_name, Storage};
+use crate::error::Error;
+
+#[derive(Debug, Clone)]
+pub struct FileSystemStorage {
+    data_dir: PathBuf,
+}
+
+impl FileSystemStorage {
+    pub fn new<P: Into<PathBuf>>(data_dir: P) -> Self {
+        Self {
+            data_dir: data_dir.into(),
+        }
+    }
+
+    /// The path of the blob, which must be right in the data dir.
+    pub fn path_of(&self, name: &str) -> Result<PathBuf, Error> {
+        check_file_name(name)?;
+        Ok(self.data_dir.join(format!("{}.safe", name)))
+    }
+}
+
+impl Storage for FileSystemStorage {
+    fn read(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let path = self.path_of(name)?;
+        if !path.exists() {
+            return Err(Error::FileNotFound(format!("{}.safe", name)));
+        }
+        std::fs::read(path).map_err(|err| {
+            Error::FailedToOpenFile(format!("Failed to open file {}: {:?}", name, err))
+        })
+    }
+
+    /// Write to a temporary file next to it first and rename it over, so
+    /// that a crash halfway never leaves a truncated file behind. The file
+    /// keeps its permissions.
+    fn write(&self, name: &str, data: &[u8]) -> Result<(), Error> {
+        let path = self.path_of(name)?;
+        let temp_path = self.data_dir.join(format!(".{}.safe.tmp", name));
+        let map_err = |err: std::io::Error| {
+            Error::FailedToWriteFile(format!("Failed to write file {}: {:?}", name, err))
+        };
+        let mut file = std::fs::File::create(&temp_path).map_err(map_err)?;
+        file.write_all(data)
+            .and_then(|_| match std::fs::metadata(&path) {
+                Ok(metadata) => file.set_permissions(metadata.permissions()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(err) => Err(err),
+            })
+            .and_then(|_| file.sync_all())
+            .and_then(|_| std::fs::rename(&temp_path, &path))
+            .map_err(|err| {
+                let _ = std::fs::remove_file(&temp_path);
+                map_err(err)
+            })
+    }
+
+    fn delete(&self, name: &str) -> Result<(), Error> {
+        std::fs::remove_file(self.path_of(name)?).map_err(|err| {
+            Error::FailedToDeleteFile(format!("Failed to delete file {}: {:?}", name, err))
+        })
+    }
+
+    fn list(&self) -> Result<Vec<String>, Error> {
+        let mut file_names = std::fs::read_dir(&self.data_dir)
+            .map_err(|err| Error::FailedToListFiles(format!("{:?}", err)))?
+            .filter_map(|e| e.ok()?.file_name().into_string().ok())
+            .filter_map(|filename| filename.strip_suffix(".safe").map(|s| s.to_string()))
+            .collect::<Vec<_>>();
+        file_names.sort();
+        Ok(file_names)
+    }
+
+    fn exists(&self, name: &str) -> Result<bool, Error> {
+        Ok(self.path_of(name)?.exists())
+    }
+}
@@ -0,0 +1,57 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use super::Storage;
+use crate::error::Error;
+
+/// Keeps the blobs in memory. Clones share the same blobs, so a test can keep
+/// one handle to inspect what the code under test has written.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    blobs: Arc<Mutex<BTreeMap<String, Vec<u8>>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read(&self, name: &str) -> Result<Vec<u8>, Error> {
+        self.blobs
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::FileNotFound(format!("{}.safe", name)))
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> Result<(), Error> {
+        self.blobs
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> Result<(), Error> {
+        self.blobs
+            .lock()
+            .unwrap()
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| Error::FileNotFound(format!("{}.safe", name)))
+    }
+
+    fn list(&self) -> Result<Vec<String>, Error> {
+        // BTreeMap keys are already sorted
+        Ok(self.blobs.lock().unwrap().keys().cloned().collect())
+    }
+
+    fn exists(&self, name: &str) -> Result<bool, Error> {
+        Ok(self.blobs.lock().unwrap().contains_key(name))
+    }
+}
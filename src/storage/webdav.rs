@@ -0,0 +1,178 @@
+use std::io::Read;
+
+use super::Storage;
+use crate::{encode::base64_encode, error::Error};
+
+/// Keeps the .safe blobs in a collection on a WebDAV server, e.g., a
+/// self-hosted Nextcloud or Apache mod_dav.
+#[derive(Debug, Clone)]
+pub struct WebDavStorage {
+    base_url: String,
+    authorization: Option<String>,
+    agent: ureq::Agent,
+}
+
+impl WebDavStorage {
+    pub fn new(url: &str, username: &str, password: &str) -> Self {
+        let base_url = if url.ends_with('/') {
+            url.to_string()
+        } else {
+            format!("{}/", url)
+        };
+        let authorization = if username.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "Basic {}",
+                base64_encode(format!("{}:{}", username, password))
+            ))
+        };
+        Self {
+            base_url,
+            authorization,
+            agent: ureq::AgentBuilder::new().build(),
+        }
+    }
+
+    fn url_of(&self, name: &str) -> String {
+        format!(
+            "{}{}",
+            self.base_url,
+            percent_encode(&format!("{}.safe", name))
+        )
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let request = self.agent.request(method, url);
+        match &self.authorization {
+            Some(authorization) => request.set("Authorization", authorization),
+            None => request,
+        }
+    }
+}
+
+fn map_error(name: &str, err: ureq::Error) -> Error {
+    match err {
+        ureq::Error::Status(404, _) => Error::FileNotFound(format!("{}.safe", name)),
+        ureq::Error::Status(code, response) => {
+            Error::WebDav(format!("{} {} for {}", code, response.status_text(), name))
+        }
+        ureq::Error::Transport(transport) => Error::WebDav(transport.to_string()),
+    }
+}
+
+impl Storage for WebDavStorage {
+    fn read(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let response = self
+            .request("GET", &self.url_of(name))
+            .call()
+            .map_err(|err| map_error(name, err))?;
+        let mut data = vec![];
+        response
+            .into_reader()
+            .read_to_end(&mut data)
+            .map_err(|err| Error::FailedToOpenFile(format!("{:?}", err)))?;
+        Ok(data)
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> Result<(), Error> {
+        self.request("PUT", &self.url_of(name))
+            .set("Content-Type", "application/octet-stream")
+            .send_bytes(data)
+            .map_err(|err| Error::FailedToWriteFile(err.to_string()))?;
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> Result<(), Error> {
+        self.request("DELETE", &self.url_of(name))
+            .call()
+            .map_err(|err| map_error(name, err))?;
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, Error> {
+        let body = self
+            .request("PROPFIND", &self.base_url)
+            .set("Depth", "1")
+            .set("Content-Type", "application/xml")
+            .send_string(
+                r#"<?xml version="1.0" encoding="utf-8"?><propfind xmlns="DAV:"><prop><resourcetype/></prop></propfind>"#,
+            )
+            .map_err(|err| Error::FailedToListFiles(err.to_string()))?
+            .into_string()
+            .map_err(|err| Error::FailedToListFiles(format!("{:?}", err)))?;
+        let mut file_names = extract_hrefs(&body)
+            .iter()
+            .filter_map(|href| {
+                let last_segment = href.trim_end_matches('/').rsplit('/').next()?;
+                percent_decode(last_segment)?
+                    .strip_suffix(".safe")
+                    .map(|s| s.to_string())
+            })
+            .collect::<Vec<_>>();
+        file_names.sort();
+        file_names.dedup();
+        Ok(file_names)
+    }
+
+    fn exists(&self, name: &str) -> Result<bool, Error> {
+        match self.request("HEAD", &self.url_of(name)).call() {
+            Ok(_) => Ok(true),
+            Err(ureq::Error::Status(404, _)) => Ok(false),
+            Err(err) => Err(map_error(name, err)),
+        }
+    }
+}
+
+/// Collect the text of all the <href> elements in a multistatus response,
+/// whatever namespace prefix the server chooses for "DAV:".
+fn extract_hrefs(body: &str) -> Vec<String> {
+    let mut hrefs = vec![];
+    let mut remained = body;
+    while let Some(start) = remained.find('<') {
+        remained = &remained[start + 1..];
+        let Some(end) = remained.find('>') else {
+            break;
+        };
+        let tag = &remained[..end];
+        remained = &remained[end + 1..];
+        let tag_name = tag.rsplit(':').next().unwrap_or(tag);
+        if tag.starts_with('/') || !tag_name.eq_ignore_ascii_case("href") {
+            continue;
+        }
+        if let Some(close) = remained.find("</") {
+            hrefs.push(remained[..close].trim().to_string());
+            remained = &remained[close..];
+        }
+    }
+    hrefs
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
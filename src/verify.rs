@@ -0,0 +1,194 @@
+use std::{collections::HashSet, fmt::Display};
+
+use crate::{
+    cipher::{decrypt_to_bytes, key_derive, parse_images, parse_plaintext},
+    data_structures::{image_digest, image_reference, IMAGE_SEP},
+    encode::base64_decode_to_bytes,
+    error::{Error, ErrorKind},
+    storage::Storage,
+};
+
+/// Something found while checking a .safe file.
+#[derive(Debug)]
+pub enum Finding {
+    /// Created but never saved. Not a problem.
+    Empty,
+    Unreadable(Error),
+    /// The file does not have the IV, ciphertext and MAC lines
+    MalformedHeader,
+    InvalidBase64(&'static str),
+    InvalidLength(&'static str),
+    /// Either the password is wrong or the file has been modified
+    MacMismatch,
+    DecryptionFailed,
+    MalformedImageSection,
+    MalformedPassages(Error),
+    /// A passage shows an image that is not in the file
+    MissingImage {
+        passage: String,
+        digest: String,
+    },
+    /// An image that no passage shows. Not a problem: the app keeps such
+    /// images until they are cleaned up from its menu.
+    UnreferencedImage {
+        digest: String,
+    },
+}
+
+impl Finding {
+    pub fn is_problem(&self) -> bool {
+        !matches!(self, Finding::Empty | Finding::UnreferencedImage { .. })
+    }
+
+    /// What kind of error the problem is, telling a wrong password apart
+    /// from a damaged file.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Finding::Unreadable(err) => err.kind(),
+            Finding::MacMismatch => ErrorKind::WrongPassword,
+            _ => ErrorKind::Corrupted,
+        }
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Finding::Empty => write!(f, "never saved"),
+            Finding::Unreadable(err) => write!(f, "cannot be read: {:?}", err),
+            Finding::MalformedHeader => write!(f, "missing the IV, ciphertext or MAC line"),
+            Finding::InvalidBase64(part) => write!(f, "the {} is not valid base64", part),
+            Finding::InvalidLength(part) => write!(f, "the {} has an invalid length", part),
+            Finding::MacMismatch => write!(f, "wrong password or the file has been modified"),
+            Finding::DecryptionFailed => write!(f, "failed to decrypt"),
+            Finding::MalformedImageSection => write!(f, "the image section is malformed"),
+            Finding::MalformedPassages(err) => write!(f, "the passages are malformed: {:?}", err),
+            Finding::MissingImage { passage, digest } => {
+                write!(f, "passage \"{}\" shows missing image {}", passage, digest)
+            }
+            Finding::UnreferencedImage { digest } => {
+                write!(f, "image {} is not shown in any passage", digest)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct VerifyReport {
+    pub file: String,
+    /// Whether the content was checked, i.e., a password was given and the
+    /// file decrypted
+    pub decrypted: bool,
+    pub findings: Vec<Finding>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        !self.findings.iter().any(Finding::is_problem)
+    }
+
+    /// The kind of the first problem, if there is one.
+    pub fn kind(&self) -> Option<ErrorKind> {
+        self.findings
+            .iter()
+            .find(|finding| finding.is_problem())
+            .map(Finding::kind)
+    }
+}
+
+/// Check a .safe file. Without a password only the layout of the file can
+/// be checked; with one, also what is inside.
+pub fn verify_file(storage: &dyn Storage, file: &str, password: Option<&str>) -> VerifyReport {
+    let mut report = VerifyReport {
+        file: file.to_string(),
+        decrypted: false,
+        findings: vec![],
+    };
+    if let Err(finding) = check(storage, file, password, &mut report) {
+        report.findings.push(finding);
+    }
+    report
+}
+
+fn check(
+    storage: &dyn Storage,
+    file: &str,
+    password: Option<&str>,
+    report: &mut VerifyReport,
+) -> Result<(), Finding> {
+    let content = storage.read(file).map_err(Finding::Unreadable)?;
+    let content =
+        String::from_utf8(content).map_err(|_| Finding::Unreadable(Error::InvalidUTF8))?;
+    if content.is_empty() {
+        return Err(Finding::Empty);
+    }
+    let lines = content.split("\n").collect::<Vec<_>>();
+    if lines.len() < 3 {
+        return Err(Finding::MalformedHeader);
+    }
+    let (iv, data, mac) = (lines[0], lines[1], lines[2]);
+    if decode("IV", iv)?.len() != 16 {
+        return Err(Finding::InvalidLength("IV"));
+    }
+    // AES blocks, at least one for the padding
+    let data_len = decode("ciphertext", data)?.len();
+    if data_len == 0 || data_len % 16 != 0 {
+        return Err(Finding::InvalidLength("ciphertext"));
+    }
+    // HMAC-SHA256
+    if decode("MAC", mac)?.len() != 32 {
+        return Err(Finding::InvalidLength("MAC"));
+    }
+
+    let Some(password) = password else {
+        return Ok(());
+    };
+    let bytes =
+        decrypt_to_bytes(&key_derive(password), iv, data, mac).map_err(|err| match err {
+            Error::MacFail(_) => Finding::MacMismatch,
+            _ => Finding::DecryptionFailed,
+        })?;
+    if let Some(i) = bytes.iter().position(|b| *b == IMAGE_SEP) {
+        parse_images(&bytes[i + 1..]).map_err(|_| Finding::MalformedImageSection)?;
+    }
+    let plaintext = parse_plaintext(bytes).map_err(Finding::MalformedPassages)?;
+    report.decrypted = true;
+
+    let digests = plaintext
+        .images()
+        .iter()
+        .map(|image| image_digest(image))
+        .collect::<HashSet<_>>();
+    let references = plaintext.image_references();
+    for passage in plaintext.passages() {
+        let mut missing = passage
+            .content()
+            .split('\n')
+            .filter_map(image_reference)
+            .filter(|digest| !digests.contains(*digest))
+            .collect::<Vec<_>>();
+        missing.sort();
+        missing.dedup();
+        report
+            .findings
+            .extend(missing.into_iter().map(|digest| Finding::MissingImage {
+                passage: passage.title().clone(),
+                digest: digest.to_string(),
+            }));
+    }
+    let mut unreferenced = digests
+        .into_iter()
+        .filter(|digest| !references.contains(digest.as_str()))
+        .collect::<Vec<_>>();
+    unreferenced.sort();
+    report.findings.extend(
+        unreferenced
+            .into_iter()
+            .map(|digest| Finding::UnreferencedImage { digest }),
+    );
+    Ok(())
+}
+
+fn decode(part: &'static str, text: &str) -> Result<Vec<u8>, Finding> {
+    base64_decode_to_bytes(text).map_err(|_| Finding::InvalidBase64(part))
+}
@@ -0,0 +1,113 @@
+#![cfg(unix)]
+
+use std::{
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+use safe_writing_rs::{
+    agent::{Agent, Client},
+    error::Error,
+};
+
+fn socket(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "safe-writing-test-agent-{}-{}.sock",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn start(path: &PathBuf, timeout: Duration) -> Client {
+    let agent = Agent::bind(path, timeout).unwrap();
+    thread::spawn(move || agent.serve(|_| {}));
+    Client::new(path)
+}
+
+#[test]
+fn agent_stores_gives_and_forgets_keys() {
+    let path = socket("keys");
+    let client = start(&path, Duration::from_secs(60));
+    assert_eq!(client.get_key("日记").unwrap(), None);
+
+    client.put_key("日记", &[7; 16]).unwrap();
+    client.put_key("b", &[8; 16]).unwrap();
+    assert_eq!(client.get_key("日记").unwrap(), Some([7; 16]));
+
+    client.forget("日记").unwrap();
+    assert_eq!(client.get_key("日记").unwrap(), None);
+    assert_eq!(client.get_key("b").unwrap(), Some([8; 16]));
+    client.lock_all().unwrap();
+    assert_eq!(client.get_key("b").unwrap(), None);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn agent_forgets_keys_after_the_timeout() {
+    let path = socket("timeout");
+    let client = start(&path, Duration::from_millis(100));
+    client.put_key("a", &[1; 16]).unwrap();
+    assert_eq!(client.get_key("a").unwrap(), Some([1; 16]));
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(client.get_key("a").unwrap(), None);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn agent_takes_over_a_stale_socket_only() {
+    let path = socket("stale");
+    // A socket file with nobody listening, as left by a killed agent
+    drop(UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+    let client = start(&path, Duration::from_secs(60));
+    client.put_key("a", &[2; 16]).unwrap();
+
+    assert!(matches!(
+        Agent::bind(&path, Duration::from_secs(60)),
+        Err(Error::Agent(_))
+    ));
+    assert_eq!(client.get_key("a").unwrap(), Some([2; 16]));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn socket_is_private() {
+    let path = socket("private");
+    let _client = start(&path, Duration::from_secs(60));
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    let dir = path.parent().unwrap();
+    let name = path.file_name().unwrap().to_string_lossy().to_string();
+    assert!(!std::fs::read_dir(dir).unwrap().any(|entry| entry
+        .unwrap()
+        .file_name()
+        .to_string_lossy()
+        .starts_with(&format!(".{}", name))));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn stalled_client_does_not_block_others() {
+    let path = socket("stalled");
+    let client = start(&path, Duration::from_secs(60));
+    // Connects and never sends a request
+    let _stalled = UnixStream::connect(&path).unwrap();
+    let start = Instant::now();
+    client.put_key("a", &[3; 16]).unwrap();
+    assert_eq!(client.get_key("a").unwrap(), Some([3; 16]));
+    assert!(start.elapsed() < Duration::from_secs(1));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn client_fails_without_an_agent() {
+    let client = Client::new(socket("none"));
+    assert!(matches!(client.get_key("a"), Err(Error::Agent(_))));
+}
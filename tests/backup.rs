@@ -0,0 +1,162 @@
+mod common;
+
+use common::TempDir;
+use safe_writing_rs::{
+    backup::{restore_backup, Backup, Manifest, OnConflict, RestoreOutcome},
+    error::Error,
+    storage::{MemoryStorage, Storage},
+};
+
+fn storage_with(files: &[(&str, &str)]) -> MemoryStorage {
+    let storage = MemoryStorage::new();
+    for (name, content) in files {
+        storage.write(name, content.as_bytes()).unwrap();
+    }
+    storage
+}
+
+fn read(storage: &MemoryStorage, name: &str) -> String {
+    String::from_utf8(storage.read(name).unwrap()).unwrap()
+}
+
+#[test]
+fn backup_round_trips_and_checks_the_password() {
+    let storage = storage_with(&[("a", "first"), ("b", "")]);
+    let backup = Backup::create(&storage, Some(b"font_size = 24.0\n".to_vec())).unwrap();
+    assert_eq!(backup.manifest.entries.len(), 3);
+    let ciphertext = backup.encrypt("backup").unwrap();
+
+    assert!(matches!(
+        Backup::decrypt("wrong", &ciphertext),
+        Err(Error::MacFail(_))
+    ));
+    let restored = Backup::decrypt("backup", &ciphertext).unwrap();
+    assert_eq!(restored.files, backup.files);
+    assert_eq!(restored.config, backup.config);
+
+    let empty = MemoryStorage::new();
+    let reports = restore_backup(&empty, &restored, None, OnConflict::Skip);
+    assert!(reports
+        .iter()
+        .all(|r| matches!(r.outcome, RestoreOutcome::Restored)));
+    assert_eq!(empty.list().unwrap(), ["a", "b"]);
+    assert_eq!(read(&empty, "a"), "first");
+}
+
+#[test]
+fn restore_handles_taken_names() {
+    let backup = Backup::create(&storage_with(&[("a", "old a"), ("b", "b")]), None).unwrap();
+    let outcomes = |storage: &MemoryStorage, on_conflict| {
+        restore_backup(storage, &backup, None, on_conflict)
+            .into_iter()
+            .map(|r| r.outcome.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let storage = storage_with(&[("a", "new a"), ("a (2)", "other"), ("b", "b")]);
+    assert_eq!(
+        outcomes(&storage, OnConflict::Skip),
+        ["skipped, the name is taken", "already there"]
+    );
+    assert_eq!(read(&storage, "a"), "new a");
+    assert_eq!(
+        outcomes(&storage, OnConflict::Rename),
+        ["restored as a (3)", "already there"]
+    );
+    assert_eq!(read(&storage, "a (3)"), "old a");
+    assert_eq!(
+        outcomes(&storage, OnConflict::Overwrite),
+        ["overwritten", "already there"]
+    );
+    assert_eq!(read(&storage, "a"), "old a");
+}
+
+#[test]
+fn damaged_backup_is_refused() {
+    let backup = Backup::create(&storage_with(&[("a", "content")]), None).unwrap();
+    let mut backup = Backup {
+        files: vec![("a".to_string(), b"changed".to_vec())],
+        ..backup
+    };
+    backup.manifest.entries[0].size = "changed".len();
+    let ciphertext = backup.encrypt("backup").unwrap();
+    assert!(matches!(
+        Backup::decrypt("backup", &ciphertext),
+        Err(Error::InvalidBackup(_))
+    ));
+}
+
+#[test]
+fn restore_refuses_names_outside_the_storage() {
+    let backup = Backup {
+        manifest: Manifest {
+            created: "".to_string(),
+            entries: vec![],
+        },
+        files: ["../outside", "a/b", "", "ok"]
+            .iter()
+            .map(|name| (name.to_string(), b"content".to_vec()))
+            .collect(),
+        config: None,
+    };
+    let storage = MemoryStorage::new();
+    let reports = restore_backup(&storage, &backup, None, OnConflict::Overwrite);
+    assert_eq!(reports.len(), 4);
+    for report in &reports[..3] {
+        assert!(matches!(
+            report.outcome,
+            RestoreOutcome::Failed(Error::InvalidFileName(_))
+        ));
+    }
+    assert!(matches!(reports[3].outcome, RestoreOutcome::Restored));
+    assert_eq!(storage.list().unwrap(), ["ok"]);
+}
+
+/// The files are restored into the storage in use, so the restored settings
+/// must not point elsewhere.
+#[test]
+fn restore_keeps_where_the_files_are() {
+    let temp = TempDir::new("backup-config");
+    let config_file = temp.0.join("config.toml");
+    std::fs::write(&config_file, "font_size = 24.0\ndata_dir = \"/local\"\n").unwrap();
+    let config = |font_size| {
+        format!(
+            "font_size = {:.1}\ndata_dir = \"/elsewhere\"\n\n[storage]\nbackend = \"webdav\"\nurl = \"https://example.com\"\n",
+            font_size
+        )
+    };
+    let backup = |font_size| Backup {
+        manifest: Manifest {
+            created: "".to_string(),
+            entries: vec![],
+        },
+        files: vec![],
+        config: Some(config(font_size).into_bytes()),
+    };
+    let restore = |font_size, on_conflict| {
+        let reports = restore_backup(
+            &MemoryStorage::new(),
+            &backup(font_size),
+            Some(&config_file),
+            on_conflict,
+        );
+        assert_eq!(reports[0].file, "config.toml");
+        reports[0].outcome.to_string()
+    };
+
+    assert_eq!(restore(30.0, OnConflict::Overwrite), "overwritten");
+    let restored: toml::Table =
+        toml::from_str(&std::fs::read_to_string(&config_file).unwrap()).unwrap();
+    assert_eq!(restored["font_size"].as_float(), Some(30.0));
+    assert_eq!(restored["data_dir"].as_str(), Some("/local"));
+    assert!(restored.get("storage").is_none());
+    assert_eq!(restore(30.0, OnConflict::Overwrite), "already there");
+
+    assert_eq!(
+        restore(18.0, OnConflict::Rename),
+        "saved as config (2).toml, which is not used; the current one is kept"
+    );
+    assert!(std::fs::read_to_string(temp.0.join("config (2).toml"))
+        .unwrap()
+        .contains("/local"));
+}
@@ -0,0 +1,125 @@
+use safe_writing_rs::{
+    data_structures::{Passage, PassageRef, PlainText, PlainTextChange},
+    error::Error,
+};
+
+/// Passages titled "A", "B", ... with contents "a", "b", ..., recording the
+/// changes.
+fn plaintext(titles: &str) -> PlainText {
+    let mut plaintext = PlainText::from_passages(
+        titles
+            .chars()
+            .map(|c| Passage::new(0, c.to_string(), c.to_lowercase().to_string()))
+            .collect(),
+    );
+    plaintext.record_changes();
+    plaintext
+}
+
+fn titles(plaintext: &PlainText) -> String {
+    plaintext
+        .passages()
+        .iter()
+        .map(|p| p.title().as_str())
+        .collect()
+}
+
+#[test]
+fn changes_are_only_kept_when_recording() {
+    let mut plaintext = PlainText::from_passages(vec![Passage::new(0, "A".into(), "".into())]);
+    plaintext.set_title(0, "B".to_string()).unwrap();
+    assert!(plaintext.take_changes().is_empty());
+
+    plaintext.record_changes();
+    plaintext.set_title(0, "C".to_string()).unwrap();
+    assert_eq!(
+        plaintext.take_changes(),
+        [PlainTextChange::TitleChanged { id: 0 }]
+    );
+    assert!(plaintext.take_changes().is_empty());
+}
+
+#[test]
+fn move_passage_shifts_the_ones_between() {
+    let mut plaintext = plaintext("ABCD");
+    plaintext.move_passage(0, 2).unwrap();
+    assert_eq!(titles(&plaintext), "BCAD");
+    plaintext.move_passage(PassageRef::Id(3), 0).unwrap();
+    assert_eq!(titles(&plaintext), "DBCA");
+    plaintext.move_passage(1, 1).unwrap();
+    assert_eq!(
+        plaintext.take_changes(),
+        [
+            PlainTextChange::Moved {
+                id: 0,
+                from: 0,
+                to: 2
+            },
+            PlainTextChange::Moved {
+                id: 3,
+                from: 3,
+                to: 0
+            },
+        ]
+    );
+    assert!(matches!(
+        plaintext.move_passage(0, 4),
+        Err(Error::PassageIndexOutOfRange(4))
+    ));
+}
+
+#[test]
+fn duplicate_passage_inserts_a_copy_after_it() {
+    let mut plaintext = plaintext("AB");
+    let id = plaintext.duplicate_passage(0).unwrap();
+    assert_eq!(id, 2);
+    assert_eq!(titles(&plaintext), "AAB");
+    assert_eq!(
+        plaintext.passage(PassageRef::Id(id)).unwrap().content(),
+        "a"
+    );
+    assert_eq!(
+        plaintext.take_changes(),
+        [PlainTextChange::Inserted { id: 2, index: 1 }]
+    );
+}
+
+#[test]
+fn merge_with_next_undoes_split_passage() {
+    let mut plaintext = plaintext("AB");
+    plaintext.set_content(0, "前半后半".to_string()).unwrap();
+    plaintext.take_changes();
+    assert!(matches!(
+        plaintext.split_passage(0, 1, "".to_string()),
+        Err(Error::InvalidSplitPosition(1))
+    ));
+
+    let id = plaintext
+        .split_passage(0, "前半".len(), "C".to_string())
+        .unwrap();
+    assert_eq!(titles(&plaintext), "ACB");
+    assert_eq!(plaintext.content_of_passage(0).unwrap(), "前半");
+    assert_eq!(plaintext.content_of_passage(1).unwrap(), "后半");
+    assert_eq!(
+        plaintext.take_changes(),
+        [
+            PlainTextChange::ContentChanged { id: 0 },
+            PlainTextChange::Inserted { id, index: 1 },
+        ]
+    );
+
+    plaintext.merge_with_next(0).unwrap();
+    assert_eq!(titles(&plaintext), "AB");
+    assert_eq!(plaintext.content_of_passage(0).unwrap(), "前半后半");
+    assert_eq!(
+        plaintext.take_changes(),
+        [
+            PlainTextChange::Removed { id, index: 1 },
+            PlainTextChange::ContentChanged { id: 0 },
+        ]
+    );
+    assert!(matches!(
+        plaintext.merge_with_next(1),
+        Err(Error::PassageIndexOutOfRange(2))
+    ));
+}
@@ -0,0 +1,157 @@
+use safe_writing_rs::{
+    data_structures::{image_digest, image_placeholder, PlainText},
+    encode::base64_encode,
+    export::{export_epub, export_html, export_markdown, export_pdf},
+};
+
+mod common;
+use common::TempDir;
+
+fn sample() -> PlainText {
+    let image = vec![0x89, b'P', b'N', b'G', 1, 2, 3];
+    let digest = image_digest(&image);
+    let mut plaintext = PlainText::empty();
+    plaintext
+        .insert_new_passage(0, "First".to_string())
+        .unwrap();
+    plaintext
+        .set_content(0, format!("Before\n{}\nAfter", image_placeholder(&digest)))
+        .unwrap();
+    plaintext
+        .insert_new_passage(1, "a/b: c?".to_string())
+        .unwrap();
+    plaintext.set_content(1, "标题 😀".to_string()).unwrap();
+    plaintext.images_mut().push(image);
+    plaintext
+}
+
+#[test]
+fn markdown_export_writes_passages_and_images() {
+    let temp = TempDir::new("markdown");
+    let plaintext = sample();
+    export_markdown(&plaintext, &temp.0).unwrap();
+
+    let digest = image_digest(&plaintext.images()[0]);
+    assert_eq!(
+        std::fs::read_to_string(temp.0.join("01 First.md")).unwrap(),
+        format!("# First\n\nBefore\n![](images/{}.png)\nAfter\n", digest)
+    );
+    assert_eq!(
+        std::fs::read_to_string(temp.0.join("02 a_b_ c_.md")).unwrap(),
+        "# a/b: c?\n\n标题 😀\n"
+    );
+    assert_eq!(
+        std::fs::read(temp.0.join("images").join(format!("{}.png", digest))).unwrap(),
+        plaintext.images()[0]
+    );
+}
+
+#[test]
+fn markdown_export_does_not_overwrite() {
+    let temp = TempDir::new("markdown-existing");
+    std::fs::write(temp.0.join("keep.txt"), "keep").unwrap();
+    assert!(export_markdown(&sample(), &temp.0).is_err());
+    assert_eq!(
+        std::fs::read_to_string(temp.0.join("keep.txt")).unwrap(),
+        "keep"
+    );
+}
+
+#[test]
+fn html_export_has_chosen_passages_and_inlined_images() {
+    let temp = TempDir::new("html");
+    let path = temp.0.join("out.html");
+    let plaintext = sample();
+    export_html(&plaintext, "My <File>", &[1, 0], &path).unwrap();
+
+    let html = std::fs::read_to_string(&path).unwrap();
+    assert!(html.contains("<title>My &lt;File&gt;</title>"));
+    assert!(html.contains("@font-face"));
+    let toc = html
+        .find("<li><a href=\"#passage-1\">a/b: c?</a></li>")
+        .unwrap();
+    assert!(
+        toc < html
+            .find("<li><a href=\"#passage-2\">First</a></li>")
+            .unwrap()
+    );
+    assert!(html.contains("<section id=\"passage-2\">\n<h2>First</h2>\n<p>Before</p>"));
+    assert!(html.contains(&format!(
+        "src=\"data:image/png;base64,{}\"",
+        base64_encode(&plaintext.images()[0])
+    )));
+    assert!(!html.contains("image!("));
+
+    assert!(export_html(&plaintext, "x", &[2], &path).is_err());
+}
+
+#[test]
+fn epub_export_has_a_chapter_per_passage() {
+    let temp = TempDir::new("epub");
+    let path = temp.0.join("out.epub");
+    let plaintext = sample();
+    export_epub(&plaintext, "Book", &[0, 1], false, &path).unwrap();
+
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+    let read = |zip: &mut zip::ZipArchive<std::fs::File>, name: &str| {
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut zip.by_name(name).unwrap(), &mut content).unwrap();
+        content
+    };
+    assert_eq!(zip.by_index(0).unwrap().name(), "mimetype");
+    assert_eq!(read(&mut zip, "mimetype"), "application/epub+zip");
+    let nav = read(&mut zip, "OEBPS/nav.xhtml");
+    assert!(nav.contains("<li><a href=\"chapter-1.xhtml\">First</a></li>"));
+    assert!(nav.contains("<li><a href=\"chapter-2.xhtml\">a/b: c?</a></li>"));
+    let chapter = read(&mut zip, "OEBPS/chapter-1.xhtml");
+    assert!(chapter.contains("<h1>First</h1>\n<p>Before</p>\n<p><img src=\"images/image-1.png\""));
+    let mut image = vec![];
+    std::io::Read::read_to_end(
+        &mut zip.by_name("OEBPS/images/image-1.png").unwrap(),
+        &mut image,
+    )
+    .unwrap();
+    assert_eq!(image, plaintext.images()[0]);
+    let package = read(&mut zip, "OEBPS/content.opf");
+    assert!(package.contains("<itemref idref="));
+    assert!(zip.by_name("OEBPS/fonts/LXGW.ttf").is_err());
+}
+
+#[test]
+fn pdf_export_puts_passages_on_their_own_pages() {
+    let temp = TempDir::new("pdf");
+    let path = temp.0.join("out.pdf");
+    let mut png = std::io::Cursor::new(vec![]);
+    image::RgbaImage::from_pixel(40, 20, image::Rgba([255, 0, 0, 128]))
+        .write_to(&mut png, image::ImageFormat::Png)
+        .unwrap();
+    let png = png.into_inner();
+    let mut plaintext = PlainText::empty();
+    plaintext
+        .insert_new_passage(0, "第一章".to_string())
+        .unwrap();
+    plaintext
+        .set_content(
+            0,
+            format!(
+                "{}\n\n{}",
+                "中文和 English words 混在一起，".repeat(40),
+                image_placeholder(&image_digest(&png))
+            ),
+        )
+        .unwrap();
+    plaintext
+        .insert_new_passage(1, "Second".to_string())
+        .unwrap();
+    plaintext.set_content(1, "Short".to_string()).unwrap();
+    plaintext.images_mut().push(png);
+
+    export_pdf(&plaintext, "Book", &[0, 1], 12.0, &path).unwrap();
+    let pdf = std::fs::read(&path).unwrap();
+    assert!(pdf.starts_with(b"%PDF"));
+    // The dictionaries of the objects are not compressed
+    let pdf = String::from_utf8_lossy(&pdf);
+    assert!(pdf.contains("/Type/Pages/Count 2/"));
+    assert!(pdf.contains("/Type/Outlines/Count 2/"));
+    assert!(pdf.contains("/Subtype/Image/Width 40/Height 20/"));
+}
@@ -0,0 +1,121 @@
+//! Files written by each historical format version, pinned so that changes
+//! to `cipher::decrypt` keep reading them. Never regenerate these files.
+
+use std::collections::BTreeMap;
+
+use safe_writing_rs::{
+    cipher::{decrypt_to_bytes, key_derive},
+    data_structures::PlainText,
+    encode::base64_decode,
+};
+
+const PASSWORD: &str = "golden";
+
+fn decrypt(ciphertext: &str) -> PlainText {
+    PlainText::decrypt(PASSWORD, ciphertext).unwrap()
+}
+
+fn titles_and_contents(plaintext: &PlainText) -> Vec<(&str, &str)> {
+    plaintext
+        .passages()
+        .iter()
+        .map(|p| (p.title().as_str(), p.content().as_str()))
+        .collect()
+}
+
+/// The format before images were supported: base64 encoded passages joined
+/// by "|", followed by ":FontSize=24".
+#[test]
+fn text_only_format() {
+    let plaintext = decrypt(include_str!("golden/v1_text.safe"));
+    assert_eq!(
+        titles_and_contents(&plaintext),
+        vec![
+            ("Title", "Content"),
+            ("标题 😀", "line1\nline2 | - :\n"),
+            ("", ""),
+        ]
+    );
+    assert_eq!(plaintext.num_images(), 0);
+}
+
+#[test]
+fn text_only_format_empty_file() {
+    let plaintext = decrypt(include_str!("golden/v1_empty.safe"));
+    assert!(plaintext.is_empty());
+    assert_eq!(plaintext.num_images(), 0);
+}
+
+/// The passages are followed by IMAGE_SEP and the image section.
+#[test]
+fn image_section_format() {
+    let plaintext = decrypt(include_str!("golden/v2_images.safe"));
+    assert_eq!(
+        titles_and_contents(&plaintext),
+        vec![(
+            "With image",
+            "Before\nimage!(0000000000000000000000000000000000000000000000000000000000000000)\nAfter"
+        )]
+    );
+    assert_eq!(
+        plaintext.images(),
+        &vec![
+            vec![0x89, b'P', b'N', b'G', 0x88, 0x00, 0xff],
+            vec![],
+            vec![0x88; 5]
+        ]
+    );
+}
+
+/// A passage may have a third "-" separated part with its metadata as
+/// base64 encoded JSON.
+#[test]
+fn metadata_format() {
+    let plaintext = decrypt(include_str!("golden/v3_metadata.safe"));
+    assert_eq!(
+        titles_and_contents(&plaintext),
+        vec![("With metadata", "Text"), ("Without", "Plain")]
+    );
+    assert_eq!(
+        plaintext.passages()[0].metadata(),
+        &BTreeMap::from([
+            ("created".to_string(), "2024-01-02T03:04:05Z".to_string()),
+            ("tags".to_string(), "日记,旅行".to_string()),
+        ])
+    );
+    assert!(plaintext.passages()[1].metadata().is_empty());
+}
+
+/// Versions before the metadata read only the title and the content of each
+/// passage, and must still open files that have it.
+#[test]
+fn metadata_format_under_two_part_reader() {
+    let lines = include_str!("golden/v3_metadata.safe")
+        .split('\n')
+        .collect::<Vec<_>>();
+    let bytes = decrypt_to_bytes(&key_derive(PASSWORD), lines[0], lines[1], lines[2]).unwrap();
+    let text = String::from_utf8(bytes).unwrap();
+    let passages = text.split(':').next().unwrap();
+    let titles_and_contents = passages
+        .split('|')
+        .map(|passage| {
+            let parts = passage.split('-').collect::<Vec<_>>();
+            (
+                base64_decode(parts[0]).unwrap(),
+                base64_decode(parts[1]).unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        titles_and_contents,
+        vec![
+            ("With metadata".to_string(), "Text".to_string()),
+            ("Without".to_string(), "Plain".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn wrong_password() {
+    assert!(PlainText::decrypt("not golden", include_str!("golden/v1_text.safe")).is_err());
+}
@@ -0,0 +1,286 @@
+use safe_writing_rs::{
+    data_structures::{
+        image_digest, image_placeholder, PlainText, METADATA_CREATED, METADATA_MODIFIED,
+        METADATA_SOURCE_ID, METADATA_TAGS,
+    },
+    encode::base64_encode,
+    export::export_markdown,
+    import::{import_day_one, import_enex, import_markdown},
+};
+
+mod common;
+use common::TempDir;
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\nnot really";
+
+#[test]
+fn markdown_import_takes_titles_and_embeds_images() {
+    let temp = TempDir::new("import-markdown");
+    std::fs::create_dir(temp.0.join("pics")).unwrap();
+    std::fs::write(temp.0.join("pics").join("a b.png"), PNG).unwrap();
+    std::fs::write(
+        temp.0.join("1.md"),
+        "---\ntitle: \"From Front Matter\"\ndate: 2024-01-01\n---\n\nText ![x](pics/a%20b.png) more\r\n",
+    )
+    .unwrap();
+    std::fs::write(
+        temp.0.join("2.markdown"),
+        "# From Heading\n\n![remote](https://example.com/a.png)\n![missing](nope.png)",
+    )
+    .unwrap();
+    std::fs::write(temp.0.join("3 From Name.txt"), "plain\n").unwrap();
+    std::fs::write(temp.0.join("ignored.json"), "{}").unwrap();
+
+    let plaintext = import_markdown(&temp.0).unwrap();
+    let digest = image_digest(PNG);
+    assert_eq!(plaintext.num_passages(), 3);
+    assert_eq!(
+        plaintext.title_of_passage(0),
+        Some("From Front Matter".to_string())
+    );
+    assert_eq!(
+        plaintext.content_of_passage(0),
+        Some(&format!("Text\n{}\nmore", image_placeholder(&digest)))
+    );
+    assert_eq!(
+        plaintext.title_of_passage(1),
+        Some("From Heading".to_string())
+    );
+    assert_eq!(
+        plaintext.content_of_passage(1),
+        Some(&"![remote](https://example.com/a.png)\n![missing](nope.png)".to_string())
+    );
+    assert_eq!(
+        plaintext.title_of_passage(2),
+        Some("3 From Name".to_string())
+    );
+    assert_eq!(plaintext.content_of_passage(2), Some(&"plain".to_string()));
+    assert_eq!(plaintext.images(), &vec![PNG.to_vec()]);
+}
+
+#[test]
+fn markdown_export_then_import_round_trips() {
+    let temp = TempDir::new("import-round-trip");
+    let dir = temp.0.join("out");
+    let digest = image_digest(PNG);
+    let mut plaintext = PlainText::empty();
+    plaintext
+        .insert_new_passage(0, "First".to_string())
+        .unwrap();
+    plaintext
+        .set_content(0, format!("Before\n{}\nAfter", image_placeholder(&digest)))
+        .unwrap();
+    plaintext.insert_new_passage(1, "第二".to_string()).unwrap();
+    plaintext.set_content(1, "标题 😀".to_string()).unwrap();
+    plaintext.images_mut().push(PNG.to_vec());
+
+    export_markdown(&plaintext, &dir).unwrap();
+    let imported = import_markdown(&dir).unwrap();
+    for index in 0..2 {
+        assert_eq!(
+            imported.title_of_passage(index),
+            plaintext.title_of_passage(index)
+        );
+        assert_eq!(
+            imported.content_of_passage(index),
+            plaintext.content_of_passage(index)
+        );
+    }
+    assert_eq!(imported.images(), plaintext.images());
+}
+
+#[test]
+fn enex_import_converts_notes() {
+    use md5::{Digest, Md5};
+
+    let temp = TempDir::new("import-enex");
+    let hash = format!("{:x}", Md5::digest(PNG));
+    let enex = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-export SYSTEM "http://xml.evernote.com/pub/evernote-export3.dtd">
+<en-export>
+<note>
+<title>Trip &amp; Notes</title>
+<content><![CDATA[<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-note SYSTEM "http://xml.evernote.com/pub/enml2.dtd">
+<en-note><h2>Day one</h2><div>Caf&eacute;&nbsp;time<br/>next</div><ul><li><en-todo checked="true"/>pack</li></ul><en-media type="image/png" hash="{}"/></en-note>]]></content>
+<created>20240131T235959Z</created>
+<updated>20240201T080000Z</updated>
+<tag>travel</tag>
+<tag>food</tag>
+<resource><data encoding="base64">
+{}
+</data><mime>image/png</mime></resource>
+</note>
+<note><title>Empty</title><content><![CDATA[<en-note></en-note>]]></content></note>
+</en-export>
+"#,
+        hash,
+        base64_encode(PNG)
+    );
+    let path = temp.0.join("notes.enex");
+    std::fs::write(&path, enex).unwrap();
+
+    let plaintext = import_enex(&path).unwrap();
+    assert_eq!(plaintext.num_passages(), 2);
+    assert_eq!(plaintext.images(), &vec![PNG.to_vec()]);
+    let passage = &plaintext.passages()[0];
+    assert_eq!(passage.title(), "Trip & Notes");
+    assert_eq!(
+        passage.content(),
+        &format!(
+            "## Day one\nCafé time\nnext\n- [x] pack\n{}",
+            image_placeholder(&image_digest(PNG))
+        )
+    );
+    assert_eq!(
+        passage.metadata()[METADATA_CREATED],
+        "2024-01-31T23:59:59+00:00"
+    );
+    assert_eq!(
+        passage.metadata()[METADATA_MODIFIED],
+        "2024-02-01T08:00:00+00:00"
+    );
+    assert_eq!(passage.metadata()[METADATA_TAGS], "travel, food");
+    assert_eq!(plaintext.passages()[1].content(), "");
+}
+
+#[test]
+fn day_one_import_titles_by_date_and_embeds_photos() {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    let temp = TempDir::new("import-day-one");
+    let path = temp.0.join("export.zip");
+    let journal = r#"{
+        "metadata": { "version": "1.0" },
+        "entries": [
+            {
+                "uuid": "B",
+                "creationDate": "2024-03-02T10:00:00Z",
+                "timeZone": "UTC",
+                "text": "Later",
+                "tags": []
+            },
+            {
+                "uuid": "A",
+                "creationDate": "2024-03-01T09:30:00Z",
+                "timeZone": "Europe/London",
+                "modifiedDate": "2024-03-01T20:00:00Z",
+                "text": "Walk 1\\. by the sea ![](dayone-moment://PHOTO1) after",
+                "tags": ["walks", "sea"],
+                "photos": [{ "identifier": "PHOTO1", "md5": "abc123", "type": "png" }]
+            }
+        ]
+    }"#;
+    let mut zip = ZipWriter::new(std::fs::File::create(&path).unwrap());
+    zip.start_file("Journal.json", SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(journal.as_bytes()).unwrap();
+    zip.start_file("photos/abc123.png", SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(PNG).unwrap();
+    zip.finish().unwrap();
+
+    let plaintext = import_day_one(&path).unwrap();
+    assert_eq!(plaintext.images(), &vec![PNG.to_vec()]);
+    let titles = (0..2)
+        .map(|i| plaintext.title_of_passage(i).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(titles, ["2024-03-01", "2024-03-02"]);
+    let passage = &plaintext.passages()[0];
+    assert_eq!(
+        passage.content(),
+        &format!(
+            "Walk 1. by the sea\n{}\nafter",
+            image_placeholder(&image_digest(PNG))
+        )
+    );
+    assert_eq!(
+        passage.metadata()[METADATA_CREATED],
+        "2024-03-01T09:30:00+00:00"
+    );
+    assert_eq!(
+        passage.metadata()[METADATA_MODIFIED],
+        "2024-03-01T20:00:00+00:00"
+    );
+    assert_eq!(passage.metadata()[METADATA_TAGS], "walks, sea");
+    assert_eq!(passage.metadata()[METADATA_SOURCE_ID], "A");
+}
+
+#[test]
+fn day_one_import_orders_by_time_and_skips_other_json() {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    let temp = TempDir::new("import-day-one-order");
+    let path = temp.0.join("export.zip");
+    // Sorted as text, the entry written in Tokyo would come last
+    let journal = r#"{
+        "entries": [
+            { "creationDate": "2024-03-01T20:00:00Z", "timeZone": "Europe/London", "text": "London" },
+            { "creationDate": "not a date", "text": "Unknown" },
+            { "creationDate": "2024-03-02T01:00:00+09:00", "timeZone": "Asia/Tokyo", "text": "Tokyo" }
+        ]
+    }"#;
+    let mut zip = ZipWriter::new(std::fs::File::create(&path).unwrap());
+    zip.start_file("Journal.json", SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(journal.as_bytes()).unwrap();
+    zip.start_file("settings.json", SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(br#"{ "theme": "dark" }"#).unwrap();
+    zip.finish().unwrap();
+
+    let plaintext = import_day_one(&path).unwrap();
+    let contents = plaintext
+        .passages()
+        .iter()
+        .map(|passage| passage.content().as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(contents, ["Tokyo", "London", "Unknown"]);
+    assert_eq!(
+        plaintext.title_of_passage(0),
+        Some("2024-03-02".to_string())
+    );
+    assert_eq!(
+        plaintext.title_of_passage(2),
+        Some("not a date".to_string())
+    );
+}
+
+/// Day One keeps the times in UTC, so an entry written just after midnight
+/// in Shanghai is from the day before in UTC.
+#[test]
+fn day_one_import_titles_by_the_date_where_written() {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    let temp = TempDir::new("import-day-one-time-zone");
+    let path = temp.0.join("export.zip");
+    let journal = r#"{
+        "entries": [
+            { "creationDate": "2024-03-01T16:30:00Z", "timeZone": "Asia/Shanghai", "text": "Shanghai" },
+            { "creationDate": "2024-03-02T03:00:00Z", "timeZone": "America/New_York", "text": "New York" }
+        ]
+    }"#;
+    let mut zip = ZipWriter::new(std::fs::File::create(&path).unwrap());
+    zip.start_file("Journal.json", SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(journal.as_bytes()).unwrap();
+    zip.finish().unwrap();
+
+    let plaintext = import_day_one(&path).unwrap();
+    assert_eq!(
+        plaintext.title_of_passage(0),
+        Some("2024-03-02".to_string())
+    );
+    assert_eq!(
+        plaintext.title_of_passage(1),
+        Some("2024-03-01".to_string())
+    );
+    assert_eq!(
+        plaintext.passages()[0].metadata()[METADATA_CREATED],
+        "2024-03-01T16:30:00+00:00"
+    );
+}
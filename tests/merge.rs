@@ -0,0 +1,75 @@
+use safe_writing_rs::{
+    data_structures::{image_digest, image_placeholder, Passage, PlainText},
+    merge::{images_of_passages, match_passages, PassageMatch},
+};
+
+fn plaintext(passages: &[(&str, &str)]) -> PlainText {
+    PlainText::from_passages(
+        passages
+            .iter()
+            .map(|(title, content)| Passage::new(0, title.to_string(), content.to_string()))
+            .collect(),
+    )
+}
+
+#[test]
+fn identical_and_similar_passages_are_found() {
+    let long = "The quick brown fox jumps over the lazy dog, again and again, all day long.";
+    let existing = plaintext(&[
+        ("Fox", long),
+        ("春", "春眠不觉晓，处处闻啼鸟。夜来风雨声，花落知多少。"),
+    ]);
+    let incoming = plaintext(&[
+        ("Fox", &format!("{}  \n", long)),
+        ("Fox", &long.replace("lazy", "lazy, sleepy")),
+        ("春", "春眠不觉晓，处处闻啼鸟。夜来风雨声，花落知多少！"),
+        ("Other", "Nothing like the rest"),
+    ]);
+
+    let matches = match_passages(&existing, &incoming);
+    assert_eq!(matches[0], PassageMatch::Identical(0));
+    assert!(
+        matches!(matches[1], PassageMatch::Similar { index: 0, similarity } if similarity < 1.0)
+    );
+    assert!(matches!(matches[2], PassageMatch::Similar { index: 1, .. }));
+    assert_eq!(matches[3], PassageMatch::New);
+}
+
+#[test]
+fn merging_twice_adds_nothing_new() {
+    let mut existing = plaintext(&[("A", "a")]);
+    let incoming = plaintext(&[("B", "b"), ("C", "c")]);
+    existing.append_passages(&incoming, &[1, 0]).unwrap();
+    assert_eq!(
+        (0..3)
+            .map(|i| existing.title_of_passage(i).unwrap())
+            .collect::<Vec<_>>(),
+        ["A", "C", "B"]
+    );
+    assert!(match_passages(&existing, &incoming)
+        .iter()
+        .all(|m| matches!(m, PassageMatch::Identical(_))));
+    assert!(existing.append_passages(&incoming, &[2]).is_err());
+}
+
+#[test]
+fn only_the_images_of_the_chosen_passages_are_taken() {
+    let (first, second) = (b"first".to_vec(), b"second".to_vec());
+    let show = |image: &[u8]| image_placeholder(&image_digest(image));
+    let incoming = PlainText::from_passages_images(
+        vec![
+            Passage::new(0, "A".to_string(), format!("{}\ntext", show(&first))),
+            Passage::new(0, "B".to_string(), show(&second)),
+            Passage::new(
+                0,
+                "C".to_string(),
+                format!("{}\n{}", show(&first), show(&first)),
+            ),
+        ],
+        vec![first.clone(), second],
+    );
+    assert_eq!(
+        images_of_passages(&incoming, &[0, 2]).unwrap(),
+        vec![&first]
+    );
+}
@@ -0,0 +1,29 @@
+use safe_writing_rs::password::PasswordSource;
+
+#[test]
+fn env_var_is_read() {
+    std::env::set_var("SAFE_WRITING_TEST_PASSWORD", "secret");
+    let source = PasswordSource::Env("SAFE_WRITING_TEST_PASSWORD".to_string());
+    assert_eq!(source.read().unwrap(), "secret");
+    assert!(PasswordSource::Env("SAFE_WRITING_TEST_UNSET".to_string())
+        .read()
+        .is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn only_first_line_of_command_output_is_used() {
+    let source = PasswordSource::Command("printf 'secret\\r\\nlogin: me\\n'".to_string());
+    assert_eq!(source.read().unwrap(), "secret");
+    assert!(PasswordSource::Command("false".to_string()).read().is_err());
+    assert!(PasswordSource::Command("true".to_string()).read().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn negative_fd_is_refused() {
+    assert!(matches!(
+        PasswordSource::Fd(-1).read(),
+        Err(safe_writing_rs::error::Error::FailedToReadPassword(_))
+    ));
+}
@@ -0,0 +1,45 @@
+use safe_writing_rs::{
+    data_structures::PlainText,
+    rekey::{rekey_files, RekeyOutcome},
+    storage::{MemoryStorage, Storage},
+};
+
+fn vault() -> MemoryStorage {
+    let storage = MemoryStorage::new();
+    let mut plaintext = PlainText::empty();
+    plaintext
+        .insert_new_passage(0, "Title".to_string())
+        .unwrap();
+    plaintext.set_content(0, "Content".to_string()).unwrap();
+    storage.save("a", &plaintext, "old").unwrap();
+    storage.save("b", &plaintext, "other").unwrap();
+    storage.write("c", b"").unwrap();
+    storage
+}
+
+fn files(storage: &MemoryStorage) -> Vec<String> {
+    storage.list().unwrap()
+}
+
+#[test]
+fn dry_run_writes_nothing() {
+    let storage = vault();
+    let before = storage.read("a").unwrap();
+    let reports = rekey_files(&storage, &files(&storage), "old", Some("new"), true);
+    assert!(matches!(reports[0].outcome, RekeyOutcome::WouldRekey));
+    assert!(matches!(reports[1].outcome, RekeyOutcome::Failed(_)));
+    assert!(matches!(reports[2].outcome, RekeyOutcome::SkippedEmpty));
+    assert_eq!(storage.read("a").unwrap(), before);
+}
+
+#[test]
+fn apply_changes_the_password_of_files_that_decrypt() {
+    let storage = vault();
+    let reports = rekey_files(&storage, &files(&storage), "old", Some("new"), false);
+    assert!(matches!(reports[0].outcome, RekeyOutcome::Rekeyed));
+    assert!(matches!(reports[1].outcome, RekeyOutcome::Failed(_)));
+    let plaintext = storage.load("a", "new").unwrap();
+    assert_eq!(plaintext.passages()[0].content(), "Content");
+    assert!(storage.load("a", "old").is_err());
+    assert!(storage.load("b", "other").is_ok());
+}
@@ -0,0 +1,133 @@
+use std::collections::HashSet;
+
+use proptest::prelude::*;
+use safe_writing_rs::{
+    cipher::parse_plaintext,
+    data_structures::{Passage, PlainText},
+    error::Error,
+};
+
+/// Arbitrary text, biased towards the characters the encoding uses as
+/// separators and towards multi-byte characters.
+fn text() -> impl Strategy<Value = String> {
+    prop_oneof![any::<String>(), "[a-z|:\\- \n中文😀🎉]{0,40}"]
+}
+
+fn passages() -> impl Strategy<Value = Vec<(String, String)>> {
+    prop::collection::vec((text(), text()), 0..8)
+}
+
+fn images() -> impl Strategy<Value = Vec<Vec<u8>>> {
+    prop::collection::vec(
+        prop_oneof![
+            prop::collection::vec(any::<u8>(), 0..256),
+            // The separator of the image section may well appear inside
+            // the images themselves
+            prop::collection::vec(Just(0x88u8), 0..16),
+        ],
+        0..6,
+    )
+}
+
+fn plaintext_of(passages: &[(String, String)], images: Vec<Vec<u8>>) -> PlainText {
+    PlainText::from_passages_images(
+        passages
+            .iter()
+            .map(|(title, content)| Passage::new(0, title.clone(), content.clone()))
+            .collect(),
+        images,
+    )
+}
+
+fn titles_and_contents(plaintext: &PlainText) -> Vec<(String, String)> {
+    plaintext
+        .passages()
+        .iter()
+        .map(|p| (p.title().clone(), p.content().clone()))
+        .collect()
+}
+
+fn ids(plaintext: &PlainText) -> Vec<usize> {
+    plaintext.passages().iter().map(|p| p.id()).collect()
+}
+
+proptest! {
+    #[test]
+    fn encode_then_parse(passages in passages(), images in images()) {
+        let plaintext = plaintext_of(&passages, images.clone());
+        let parsed = parse_plaintext(plaintext.encode()).unwrap();
+        prop_assert_eq!(titles_and_contents(&parsed), passages);
+        prop_assert_eq!(parsed.images(), &images);
+    }
+
+    #[test]
+    fn encrypt_then_decrypt(passages in passages(), password in text()) {
+        let plaintext = plaintext_of(&passages, vec![]);
+        let decrypted = PlainText::decrypt(&password, &plaintext.encrypt(&password)).unwrap();
+        prop_assert_eq!(titles_and_contents(&decrypted), passages);
+        prop_assert_eq!(decrypted.num_images(), 0);
+    }
+
+    #[test]
+    fn encrypt_then_decrypt_with_images(passages in passages(), images in images()) {
+        let plaintext = plaintext_of(&passages, images.clone());
+        let decrypted = PlainText::decrypt("password", &plaintext.encrypt("password")).unwrap();
+        prop_assert_eq!(titles_and_contents(&decrypted), passages);
+        prop_assert_eq!(decrypted.images(), &images);
+    }
+
+    #[test]
+    fn append_plaintext_keeps_order_and_ids_unique(a in passages(), b in passages()) {
+        let mut plaintext = plaintext_of(&a, vec![]);
+        let other = plaintext_of(&b, vec![]);
+        plaintext.append_plaintext(&other);
+
+        let expected = a.iter().chain(b.iter()).cloned().collect::<Vec<_>>();
+        prop_assert_eq!(titles_and_contents(&plaintext), expected);
+        let ids = ids(&plaintext);
+        prop_assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+        // The other file is left alone
+        prop_assert_eq!(titles_and_contents(&other), b);
+    }
+
+    #[test]
+    fn swap_moves_exactly_two_passages(passages in passages(), a in 0..10usize, b in 0..10usize) {
+        let mut plaintext = plaintext_of(&passages, vec![]);
+        let before = ids(&plaintext);
+        let result = plaintext.swap(a, b);
+        let after = ids(&plaintext);
+
+        if a < passages.len() && b < passages.len() {
+            prop_assert!(result.is_ok());
+            let mut expected = before.clone();
+            expected.swap(a, b);
+            prop_assert_eq!(&after, &expected);
+            // Swapping back restores the order
+            plaintext.swap(a, b).unwrap();
+            prop_assert_eq!(ids(&plaintext), before);
+        } else {
+            prop_assert!(matches!(result, Err(Error::PassageIndexOutOfRange(_))));
+            prop_assert_eq!(after, before);
+        }
+    }
+
+    #[test]
+    fn metadata_survives_encoding(
+        passages in passages(),
+        entries in prop::collection::btree_map(text(), text(), 0..4),
+    ) {
+        let plaintext = PlainText::from_passages(
+            passages
+                .iter()
+                .map(|(title, content)| {
+                    Passage::new(0, title.clone(), content.clone()).with_metadata(entries.clone())
+                })
+                .collect(),
+        );
+        let parsed = parse_plaintext(plaintext.encode()).unwrap();
+        prop_assert_eq!(titles_and_contents(&parsed), passages);
+        for passage in parsed.passages() {
+            prop_assert_eq!(passage.metadata(), &entries);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use safe_writing_rs::{
+    data_structures::{
+        Passage, METADATA_CREATED, METADATA_MODIFIED, METADATA_SOURCE_ID, METADATA_TAGS,
+    },
+    error::Error,
+    safe_note::{
+        decrypt_safe_notes_ciphertext, encrypt_safe_notes_plaintext, parse_safe_note_file,
+        serialize_safe_note_file, SafeNoteFile,
+    },
+};
+
+#[test]
+fn safe_notes_ciphertext_round_trips() {
+    let ciphertext = encrypt_safe_notes_plaintext("pw", "标题 😀");
+    assert_ne!(ciphertext, encrypt_safe_notes_plaintext("pw", "标题 😀"));
+    assert_eq!(
+        decrypt_safe_notes_ciphertext("pw", &ciphertext).unwrap(),
+        "标题 😀"
+    );
+    assert!(decrypt_safe_notes_ciphertext("wrong", &ciphertext).is_err());
+}
+
+#[test]
+fn safe_notes_file_round_trips() {
+    let passages = [
+        Passage::new(0, "First".to_string(), "one\ntwo".to_string()),
+        Passage::new(1, "".to_string(), "".to_string()),
+    ];
+    let json = serialize_safe_note_file("pw", &SafeNoteFile::from_passages(&passages));
+    assert!(!json.contains("First"));
+
+    let plaintext = parse_safe_note_file("pw", &json)
+        .unwrap()
+        .file
+        .into_plaintext();
+    assert_eq!(plaintext.num_passages(), 2);
+    assert_eq!(plaintext.title_of_passage(0), Some("First".to_string()));
+    assert_eq!(
+        plaintext.content_of_passage(0),
+        Some(&"one\ntwo".to_string())
+    );
+    assert_eq!(plaintext.title_of_passage(1), Some("".to_string()));
+}
+
+#[test]
+fn safe_notes_import_skips_bad_records_and_keeps_fields() {
+    let title = encrypt_safe_notes_plaintext("pw", "Kept");
+    let description = encrypt_safe_notes_plaintext("pw", "body");
+    let json = format!(
+        r#"{{"records": [
+            {{"title": "{title}", "description": "{description}", "_id": 7,
+              "createdTime": 1700000000000, "categories": ["a", "b"], "isArchive": false}},
+            {{"title": "bm90IHNhbHRlZA==", "description": "{description}"}},
+            {{"title": "{title}"}},
+            {{"title": "{}", "description": "{description}"}}
+        ], "total": 4}}"#,
+        encrypt_safe_notes_plaintext("other", "x")
+    );
+    let import = parse_safe_note_file("pw", &json).unwrap();
+    assert_eq!(
+        import
+            .errors
+            .iter()
+            .map(|err| err.index)
+            .collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert!(matches!(import.errors[0].error, Error::InvalidSafeNotes(_)));
+    assert!(matches!(
+        import.errors[1].error,
+        Error::FailedToParseJson(_)
+    ));
+
+    let passages = import.file.into_passages();
+    assert_eq!(passages.len(), 1);
+    assert_eq!(passages[0].title(), "Kept");
+    let metadata = passages[0].metadata();
+    assert_eq!(metadata[METADATA_SOURCE_ID], "7");
+    assert_eq!(metadata[METADATA_CREATED], "2023-11-14T22:13:20+00:00");
+    assert_eq!(metadata[METADATA_TAGS], "a, b");
+    assert_eq!(metadata["isArchive"], "false");
+
+    // The fields are written back
+    let json = serialize_safe_note_file("pw", &SafeNoteFile::from_passages(&passages));
+    let again = parse_safe_note_file("pw", &json)
+        .unwrap()
+        .file
+        .into_passages();
+    assert_eq!(again[0].metadata(), metadata);
+}
+
+/// Exporting an imported note writes its fields back with their own names
+/// and types, and only the ones changed since as text.
+#[test]
+fn safe_notes_fields_round_trip_unchanged() {
+    let source = serde_json::json!({
+        "title": encrypt_safe_notes_plaintext("pw", "Kept"),
+        "description": encrypt_safe_notes_plaintext("pw", "body"),
+        "_id": 7,
+        "createdTime": 1700000000000i64,
+        "updatedAt": "2024-01-01",
+        "categories": ["a", "b"],
+        "isArchive": false,
+        "color": null,
+        "position": {"x": 1.5},
+    });
+    let json = serde_json::json!({ "records": [source.clone()] }).to_string();
+    let passages = parse_safe_note_file("pw", &json)
+        .unwrap()
+        .file
+        .into_passages();
+
+    let exported: serde_json::Value = serde_json::from_str(&serialize_safe_note_file(
+        "pw",
+        &SafeNoteFile::from_passages(&passages),
+    ))
+    .unwrap();
+    let record = exported["records"][0].as_object().unwrap();
+    let source = source.as_object().unwrap();
+    assert_eq!(
+        record.keys().collect::<Vec<_>>(),
+        source.keys().collect::<Vec<_>>()
+    );
+    for (field, value) in source {
+        if field != "title" && field != "description" {
+            assert_eq!(&record[field], value, "{}", field);
+        }
+    }
+
+    let mut metadata = passages[0].metadata().clone();
+    metadata.insert(METADATA_TAGS.to_string(), "c".to_string());
+    let edited = Passage::new(0, "Kept".to_string(), "body".to_string()).with_metadata(metadata);
+    let exported: serde_json::Value = serde_json::from_str(&serialize_safe_note_file(
+        "pw",
+        &SafeNoteFile::from_passages([&edited]),
+    ))
+    .unwrap();
+    let record = &exported["records"][0];
+    assert_eq!(record["category"], "c");
+    assert!(record.get("categories").is_none());
+    assert_eq!(record["createdTime"], 1700000000000i64);
+}
+
+#[test]
+fn safe_notes_times_out_of_range_are_not_read() {
+    let json = serde_json::json!({ "records": [{
+        "title": encrypt_safe_notes_plaintext("pw", "x"),
+        "description": "",
+        "createdTime": i64::MIN,
+        "editedTime": i64::MAX,
+    }]})
+    .to_string();
+    let passages = parse_safe_note_file("pw", &json)
+        .unwrap()
+        .file
+        .into_passages();
+    assert!(!passages[0].metadata().contains_key(METADATA_CREATED));
+    assert!(!passages[0].metadata().contains_key(METADATA_MODIFIED));
+}
+
+#[test]
+fn safe_notes_import_fails_when_nothing_decrypts() {
+    let json = format!(
+        r#"{{"records": [{{"title": "{}", "description": ""}}]}}"#,
+        encrypt_safe_notes_plaintext("pw", "x")
+    );
+    assert!(parse_safe_note_file("wrong", &json).is_err());
+    assert!(matches!(
+        parse_safe_note_file("pw", r#"{"notes": []}"#),
+        Err(Error::InvalidSafeNotes(_))
+    ));
+}
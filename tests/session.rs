@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use safe_writing_rs::{
+    app::session::{Frame, LockReason, Session},
+    config::AutoLockConfig,
+};
+
+/// A clock that only moves when told to.
+struct Clock {
+    now: Instant,
+    wall_now: SystemTime,
+}
+
+impl Clock {
+    fn new() -> Self {
+        Self {
+            now: Instant::now(),
+            wall_now: SystemTime::now(),
+        }
+    }
+
+    /// Time passing while the machine runs
+    fn run(&mut self, seconds: u64) {
+        self.now += Duration::from_secs(seconds);
+        self.wall_now += Duration::from_secs(seconds);
+    }
+
+    /// Time passing while the machine sleeps, which the monotonic clock does
+    /// not count
+    fn sleep(&mut self, seconds: u64) {
+        self.wall_now += Duration::from_secs(seconds);
+    }
+
+    fn frame(&self, active: bool) -> Frame {
+        Frame {
+            now: self.now,
+            wall_now: self.wall_now,
+            active,
+            focused: Some(true),
+        }
+    }
+}
+
+fn config(idle_seconds: u64, on_suspend: bool) -> AutoLockConfig {
+    AutoLockConfig {
+        idle_seconds,
+        on_focus_loss: false,
+        on_suspend,
+    }
+}
+
+#[test]
+fn auto_lock_is_off_by_default() {
+    let config = AutoLockConfig::default();
+    let (mut session, mut clock) = (Session::default(), Clock::new());
+    session.check(&clock.frame(false), &config);
+    clock.run(24 * 3600);
+    clock.sleep(24 * 3600);
+    assert_eq!(session.check(&clock.frame(false), &config), None);
+    assert_eq!(session.next_check(&clock.frame(false), &config), None);
+}
+
+#[test]
+fn idle_expires_after_the_last_input() {
+    let config = config(300, false);
+    let (mut session, mut clock) = (Session::default(), Clock::new());
+    assert_eq!(session.check(&clock.frame(false), &config), None);
+    clock.run(200);
+    assert_eq!(session.check(&clock.frame(true), &config), None);
+    clock.run(299);
+    assert_eq!(session.check(&clock.frame(false), &config), None);
+    assert_eq!(
+        session.next_check(&clock.frame(false), &config),
+        Some(Duration::from_secs(1))
+    );
+    clock.run(1);
+    assert_eq!(
+        session.check(&clock.frame(false), &config),
+        Some(LockReason::Idle)
+    );
+}
+
+#[test]
+fn suspend_is_told_from_the_wall_clock_running_ahead() {
+    let config = config(0, true);
+    let (mut session, mut clock) = (Session::default(), Clock::new());
+    assert_eq!(session.check(&clock.frame(false), &config), None);
+    // A small adjustment of the wall clock is not a suspend
+    clock.run(10);
+    clock.sleep(5);
+    assert_eq!(session.check(&clock.frame(false), &config), None);
+    clock.run(1);
+    clock.sleep(3600);
+    assert_eq!(
+        session.check(&clock.frame(false), &config),
+        Some(LockReason::Suspended)
+    );
+
+    session.reset();
+    clock.sleep(3600);
+    assert_eq!(session.check(&clock.frame(false), &config), None);
+}
@@ -0,0 +1,217 @@
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use safe_writing_rs::{
+    error::Error,
+    storage::{FileSystemStorage, MemoryStorage, Storage, WebDavStorage},
+};
+
+mod common;
+use common::TempDir;
+
+type Files = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+/// A WebDAV server good enough for `WebDavStorage`, serving the collection
+/// /dav/ from memory. Requests without the basic credentials of user:pass
+/// are refused.
+fn stand_in() -> (String, Files) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/dav", listener.local_addr().unwrap());
+    let files = Files::default();
+    let served = files.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            serve(stream, &served);
+        }
+    });
+    (url, files)
+}
+
+fn serve(mut stream: TcpStream, files: &Files) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap(), parts.next().unwrap());
+    let (mut content_length, mut authorized) = (0, false);
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).unwrap();
+        let Some((key, value)) = header.trim_end().split_once(": ") else {
+            break;
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "content-length" => content_length = value.parse().unwrap(),
+            // "user:pass" in base64
+            "authorization" => authorized = value == "Basic dXNlcjpwYXNz",
+            _ => {}
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+
+    let name = path.strip_prefix("/dav/").unwrap_or_default().to_string();
+    let mut files = files.lock().unwrap();
+    let (status, body) = match method {
+        _ if !authorized => ("401 Unauthorized", vec![]),
+        "GET" | "HEAD" => match files.get(&name) {
+            Some(data) if method == "GET" => ("200 OK", data.clone()),
+            Some(_) => ("200 OK", vec![]),
+            None => ("404 Not Found", vec![]),
+        },
+        "PUT" => {
+            files.insert(name, body);
+            ("201 Created", vec![])
+        }
+        "DELETE" => match files.remove(&name) {
+            Some(_) => ("204 No Content", vec![]),
+            None => ("404 Not Found", vec![]),
+        },
+        "PROPFIND" => {
+            let responses = std::iter::once(String::new())
+                .chain(files.keys().cloned())
+                .map(|name| format!("<d:response><d:href>/dav/{}</d:href></d:response>", name))
+                .collect::<String>();
+            (
+                "207 Multi-Status",
+                format!(
+                    r#"<d:multistatus xmlns:d="DAV:">{}</d:multistatus>"#,
+                    responses
+                )
+                .into_bytes(),
+            )
+        }
+        _ => ("405 Method Not Allowed", vec![]),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    )
+    .unwrap();
+    stream.write_all(&body).unwrap();
+}
+
+#[test]
+fn webdav_lists_reads_writes_and_deletes() {
+    let (url, files) = stand_in();
+    let storage = WebDavStorage::new(&url, "user", "pass");
+    assert!(storage.list().unwrap().is_empty());
+
+    storage.write("日记 1", b"first").unwrap();
+    storage.write("b", b"second").unwrap();
+    assert!(files
+        .lock()
+        .unwrap()
+        .contains_key("%E6%97%A5%E8%AE%B0%201.safe"));
+    assert_eq!(storage.list().unwrap(), ["b", "日记 1"]);
+    assert_eq!(storage.read("日记 1").unwrap(), b"first");
+    assert!(storage.exists("b").unwrap());
+
+    storage.write("b", b"replaced").unwrap();
+    assert_eq!(storage.read("b").unwrap(), b"replaced");
+    storage.delete("b").unwrap();
+    assert!(!storage.exists("b").unwrap());
+    assert_eq!(storage.list().unwrap(), ["日记 1"]);
+}
+
+#[test]
+fn webdav_maps_missing_files_and_refusals() {
+    let (url, _) = stand_in();
+    let storage = WebDavStorage::new(&url, "user", "pass");
+    assert!(matches!(
+        storage.read("missing"),
+        Err(Error::FileNotFound(_))
+    ));
+    assert!(matches!(
+        storage.delete("missing"),
+        Err(Error::FileNotFound(_))
+    ));
+
+    let storage = WebDavStorage::new(&url, "user", "wrong");
+    assert!(matches!(storage.read("missing"), Err(Error::WebDav(_))));
+    assert!(matches!(storage.list(), Err(Error::FailedToListFiles(_))));
+}
+
+#[test]
+fn file_system_refuses_names_outside_the_data_dir() {
+    let temp = TempDir::new("names");
+    let storage = FileSystemStorage::new(&temp.0);
+    for name in ["", "..", "../x", "a/b", "a\\b"] {
+        assert!(matches!(
+            storage.write(name, b"content"),
+            Err(Error::InvalidFileName(_))
+        ));
+        assert!(matches!(storage.read(name), Err(Error::InvalidFileName(_))));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn file_system_write_keeps_the_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new("permissions");
+    let storage = FileSystemStorage::new(&temp.0);
+    storage.write("a", b"first").unwrap();
+    let path = temp.0.join("a.safe");
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    storage.write("a", b"second").unwrap();
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert_eq!(storage.read("a").unwrap(), b"second");
+}
+
+/// A storage that cannot tell whether a file exists, as a WebDAV server
+/// refusing HEAD, and remembers what is written to it.
+#[derive(Default)]
+struct Unsure {
+    written: Mutex<Vec<String>>,
+}
+
+impl Storage for Unsure {
+    fn read(&self, name: &str) -> Result<Vec<u8>, Error> {
+        Err(Error::FileNotFound(name.to_string()))
+    }
+
+    fn write(&self, name: &str, _: &[u8]) -> Result<(), Error> {
+        self.written.lock().unwrap().push(name.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> Result<(), Error> {
+        Err(Error::FileNotFound(name.to_string()))
+    }
+
+    fn list(&self) -> Result<Vec<String>, Error> {
+        Err(Error::WebDav("403 Forbidden".to_string()))
+    }
+
+    fn exists(&self, _: &str) -> Result<bool, Error> {
+        Err(Error::WebDav("405 Method Not Allowed".to_string()))
+    }
+}
+
+#[test]
+fn create_refuses_taken_and_unknown_names() {
+    let storage = Unsure::default();
+    assert!(matches!(storage.create("a"), Err(Error::WebDav(_))));
+    assert!(matches!(
+        storage.create("a/b"),
+        Err(Error::InvalidFileName(_))
+    ));
+    assert!(storage.written.lock().unwrap().is_empty());
+
+    let storage = MemoryStorage::new();
+    storage.write("a", b"secret").unwrap();
+    assert!(matches!(storage.create("a"), Err(Error::FileExists(_))));
+    assert_eq!(storage.read("a").unwrap(), b"secret");
+    storage.create("b").unwrap();
+    assert_eq!(storage.read("b").unwrap(), b"");
+}
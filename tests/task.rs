@@ -0,0 +1,29 @@
+use eframe::egui;
+use safe_writing_rs::{app::task::Task, error::Error};
+
+#[test]
+fn panicking_task_resolves_to_an_error() {
+    let ctx = egui::Context::default();
+    let task = Task::spawn(&ctx, || -> Result<(), Error> { panic!("broken") });
+    assert!(matches!(
+        task.wait_result(),
+        Err(Error::TaskPanicked(message)) if message == "broken"
+    ));
+
+    let task = Task::spawn(&ctx, || -> usize { panic!("{}", 42) });
+    let result = loop {
+        if let Some(result) = task.poll() {
+            break result;
+        }
+        std::thread::yield_now();
+    };
+    assert!(matches!(result, Err(Error::TaskPanicked(message)) if message == "42"));
+}
+
+#[test]
+fn task_result_is_taken_once() {
+    let ctx = egui::Context::default();
+    let task = Task::spawn(&ctx, || Ok::<_, Error>(1));
+    assert!(matches!(task.wait_result(), Ok(1)));
+    assert!(task.poll().is_none());
+}
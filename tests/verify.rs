@@ -0,0 +1,134 @@
+use safe_writing_rs::{
+    data_structures::{image_placeholder, PlainText},
+    error::ErrorKind,
+    storage::{MemoryStorage, Storage},
+    verify::{verify_file, Finding},
+};
+
+fn storage_with(name: &str, ciphertext: &str) -> MemoryStorage {
+    let storage = MemoryStorage::new();
+    storage.write(name, ciphertext.as_bytes()).unwrap();
+    storage
+}
+
+#[test]
+fn golden_file_is_ok() {
+    let storage = storage_with("v1", include_str!("golden/v1_text.safe"));
+    let report = verify_file(&storage, "v1", Some("golden"));
+    assert!(report.decrypted);
+    assert!(report.is_ok(), "{:?}", report.findings);
+}
+
+/// The pinned file has a placeholder with no image and images that are not
+/// shown anywhere.
+#[test]
+fn golden_file_with_images_has_findings() {
+    let storage = storage_with("v2", include_str!("golden/v2_images.safe"));
+    let report = verify_file(&storage, "v2", Some("golden"));
+    assert!(report.decrypted);
+    assert_eq!(
+        report
+            .findings
+            .iter()
+            .filter(|f| matches!(f, Finding::MissingImage { .. }))
+            .count(),
+        1
+    );
+    assert_eq!(
+        report
+            .findings
+            .iter()
+            .filter(|f| matches!(f, Finding::UnreferencedImage { .. }))
+            .count(),
+        3
+    );
+}
+
+#[test]
+fn wrong_password_is_told_apart_from_damage() {
+    let storage = storage_with("v1", include_str!("golden/v1_text.safe"));
+    let report = verify_file(&storage, "v1", Some("wrong"));
+    assert!(matches!(report.findings[..], [Finding::MacMismatch]));
+    assert_eq!(report.kind(), Some(ErrorKind::WrongPassword));
+    assert_eq!(verify_file(&storage, "v1", Some("golden")).kind(), None);
+
+    let storage = storage_with("broken", "not base64\nAAAA\nAAAA");
+    let report = verify_file(&storage, "broken", None);
+    assert_eq!(report.kind(), Some(ErrorKind::Corrupted));
+    let report = verify_file(&storage, "missing", None);
+    assert_eq!(report.kind(), Some(ErrorKind::NotFound));
+}
+
+#[test]
+fn layout_is_checked_without_password() {
+    let storage = storage_with("v1", include_str!("golden/v1_text.safe"));
+    let report = verify_file(&storage, "v1", None);
+    assert!(!report.decrypted);
+    assert!(report.is_ok());
+
+    let storage = storage_with("broken", "not base64\nAAAA\nAAAA");
+    let report = verify_file(&storage, "broken", None);
+    assert!(matches!(
+        report.findings[..],
+        [Finding::InvalidBase64("IV")]
+    ));
+}
+
+#[test]
+fn tampered_file_fails_the_mac() {
+    let mut lines = include_str!("golden/v1_text.safe")
+        .split('\n')
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>();
+    // Flip the first block of the ciphertext
+    let first = if lines[1].starts_with('A') { "B" } else { "A" };
+    lines[1].replace_range(0..1, first);
+    let storage = storage_with("v1", &lines.join("\n"));
+    let report = verify_file(&storage, "v1", Some("golden"));
+    assert!(matches!(report.findings[..], [Finding::MacMismatch]));
+}
+
+#[test]
+fn missing_and_unreferenced_images_are_found() {
+    let storage = MemoryStorage::new();
+    let mut plaintext = PlainText::empty();
+    plaintext
+        .insert_new_passage(0, "Title".to_string())
+        .unwrap();
+    let missing = "0".repeat(64);
+    plaintext
+        .set_content(0, format!("text\n{}", image_placeholder(&missing)))
+        .unwrap();
+    plaintext.images_mut().push(vec![1, 2, 3]);
+    storage.save("images", &plaintext, "password").unwrap();
+
+    let report = verify_file(&storage, "images", Some("password"));
+    assert!(!report.is_ok());
+    assert!(matches!(
+        &report.findings[..],
+        [
+            Finding::MissingImage { passage, digest },
+            Finding::UnreferencedImage { .. },
+        ] if passage == "Title" && *digest == missing
+    ));
+}
+
+/// The app keeps images no passage shows, so they must not fail the file.
+#[test]
+fn unreferenced_images_are_only_a_warning() {
+    let storage = MemoryStorage::new();
+    let mut plaintext = PlainText::empty();
+    plaintext
+        .insert_new_passage(0, "Title".to_string())
+        .unwrap();
+    plaintext.images_mut().push(vec![1, 2, 3]);
+    storage.save("images", &plaintext, "password").unwrap();
+
+    let report = verify_file(&storage, "images", Some("password"));
+    assert!(matches!(
+        report.findings[..],
+        [Finding::UnreferencedImage { .. }]
+    ));
+    assert!(report.is_ok());
+    assert_eq!(report.kind(), None);
+}